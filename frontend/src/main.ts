@@ -97,6 +97,61 @@ function renderJsonl(text: string) {
   app.appendChild(pre);
 }
 
+type RelationLink = { uuid: string; kind: string; title: string | null };
+
+function relationLink(link: RelationLink): string {
+  const label = escapeHtml(link.title ?? link.uuid);
+  return `<a href="/h/${encodeURIComponent(link.uuid)}">${label}</a> <span class="kind">${escapeHtml(link.kind)}</span>`;
+}
+
+async function renderRelations(apiBase: string, uuid: string, access: URLSearchParams) {
+  const qs = access.toString();
+  const res = await fetch(`${apiBase}/${uuid}/meta${qs ? `?${qs}` : ""}`, {
+    credentials: "include",
+  });
+  if (!res.ok) return;
+  const info = (await res.json()) as { parent: RelationLink | null; children: RelationLink[] };
+  if (!info.parent && info.children.length === 0) return;
+
+  const nav = document.createElement("nav");
+  nav.className = "relations";
+  const parts: string[] = [];
+  if (info.parent) parts.push(`Parent: ${relationLink(info.parent)}`);
+  if (info.children.length) {
+    parts.push(`Children: ${info.children.map(relationLink).join(", ")}`);
+  }
+  nav.innerHTML = parts.join(" · ");
+  meta.appendChild(nav);
+}
+
+// Prompts for the passphrase of a protected request and exchanges it for an unlock
+// cookie. Returns false when the request is not passphrase protected or the prompt
+// was dismissed.
+async function unlock(
+  apiBase: string,
+  uuid: string,
+  access: URLSearchParams,
+  res: Response,
+): Promise<boolean> {
+  const body = await res.json().catch(() => null);
+  if (body?.error !== "passphrase_required") return false;
+  const qs = access.toString();
+  for (;;) {
+    const passphrase = window.prompt("This request is protected. Enter the passphrase:");
+    if (!passphrase) return false;
+    const unlocked = await fetch(`${apiBase}/${uuid}/unlock${qs ? `?${qs}` : ""}`, {
+      method: "POST",
+      credentials: "include",
+      headers: { "content-type": "application/json" },
+      body: JSON.stringify({ passphrase }),
+    });
+    if (unlocked.ok) return true;
+    window.alert(
+      unlocked.status === 429 ? "Too many attempts, try again shortly." : "Incorrect passphrase.",
+    );
+  }
+}
+
 function parseTarget() {
   const path = window.location.pathname.replace(/^\/h\/?/, "");
   const isFront = path === "";
@@ -104,22 +159,37 @@ function parseTarget() {
 
   const params = new URLSearchParams(window.location.search);
   const rev = params.get("rev");
+  // Unlisted requests need their share token; signed share links carry link/expires/sig.
+  const access = new URLSearchParams();
+  for (const key of ["token", "link", "expires", "sig"]) {
+    const value = params.get(key);
+    if (value) access.set(key, value);
+  }
 
-  return { isFront, uuid, rev };
+  return { isFront, uuid, rev, access };
 }
 
 async function load() {
-  const { isFront, uuid, rev } = parseTarget();
+  const { isFront, uuid, rev, access } = parseTarget();
   const apiBase = (import.meta as any).env.VITE_API_BASE ?? "";
   const target = isFront ? "/" : `/${uuid}`;
-  const url = rev ? `${target}?rev=${encodeURIComponent(rev)}` : target;
+  const query = new URLSearchParams();
+  if (rev) query.set("rev", rev);
+  access.forEach((value, key) => query.set(key, value));
+  if (!isFront) query.set("front_matter", "strip");
+  const qs = query.toString();
+  const url = qs ? `${target}?${qs}` : target;
 
   meta.textContent = isFront
     ? "Front page"
     : `UUID: ${uuid}${rev ? ` (rev ${rev})` : ""}`;
 
   try {
-    const res = await fetch(`${apiBase}${url}`);
+    const res = await fetch(`${apiBase}${url}`, { credentials: "include" });
+    if (res.status === 401 && uuid && (await unlock(apiBase, uuid, access, res))) {
+      window.location.reload();
+      return;
+    }
     if (!res.ok) {
       app.innerHTML = `<div class="error">Failed to load: ${res.status}</div>`;
       return;
@@ -133,6 +203,10 @@ async function load() {
     } else {
       renderJsonl(text);
     }
+
+    if (uuid) {
+      await renderRelations(apiBase, uuid, access).catch(() => undefined);
+    }
   } finally {
     document.body.classList.add("loaded");
   }
@@ -0,0 +1,6 @@
+-- A revision's content is the concatenation of the objects in segment_keys. Appended
+-- revisions reuse the previous revision's segments and add one small object.
+ALTER TABLE request_revisions
+    ADD COLUMN segment_keys TEXT[] NOT NULL DEFAULT '{}';
+
+UPDATE request_revisions SET segment_keys = ARRAY[object_key];
@@ -0,0 +1,14 @@
+-- Provenance for excerpts: the revision and range a derived request was cut from.
+-- source_uuid is deliberately not a foreign key so provenance survives source deletion.
+CREATE TABLE request_sources (
+    request_uuid UUID PRIMARY KEY REFERENCES requests(uuid) ON DELETE CASCADE,
+    source_uuid UUID NOT NULL,
+    source_rev INT NOT NULL,
+    source_sha256 TEXT NOT NULL,
+    range_kind TEXT NOT NULL CHECK (range_kind IN ('lines', 'messages')),
+    range_start INT NOT NULL,
+    range_end INT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX request_sources_source_idx ON request_sources (source_uuid);
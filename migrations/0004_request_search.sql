@@ -0,0 +1,8 @@
+-- Full-text search over the latest revision of each request. search_text is filled in by
+-- the application on upload; requests created earlier are indexed on their next revision.
+ALTER TABLE requests
+    ADD COLUMN search_text TEXT NOT NULL DEFAULT '',
+    ADD COLUMN search_vector tsvector
+        GENERATED ALWAYS AS (to_tsvector('english', search_text)) STORED;
+
+CREATE INDEX requests_search_vector_idx ON requests USING GIN (search_vector);
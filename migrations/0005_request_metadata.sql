@@ -0,0 +1,8 @@
+-- Free-form client metadata, stored per revision with the latest copy on requests.
+ALTER TABLE request_revisions
+    ADD COLUMN metadata JSONB NOT NULL DEFAULT '{}';
+
+ALTER TABLE requests
+    ADD COLUMN metadata JSONB NOT NULL DEFAULT '{}';
+
+CREATE INDEX requests_metadata_idx ON requests USING GIN (metadata jsonb_path_ops);
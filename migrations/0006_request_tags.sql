@@ -0,0 +1,8 @@
+CREATE TABLE request_tags (
+    request_uuid UUID NOT NULL REFERENCES requests(uuid) ON DELETE CASCADE,
+    tag TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (request_uuid, tag)
+);
+
+CREATE INDEX request_tags_tag_idx ON request_tags (tag);
@@ -0,0 +1,11 @@
+-- Derived title and plain-text preview per revision; requests keep the latest copy plus
+-- optional user overrides.
+ALTER TABLE request_revisions
+    ADD COLUMN title TEXT,
+    ADD COLUMN summary TEXT;
+
+ALTER TABLE requests
+    ADD COLUMN title TEXT,
+    ADD COLUMN summary TEXT,
+    ADD COLUMN title_override TEXT,
+    ADD COLUMN summary_override TEXT;
@@ -0,0 +1,3 @@
+-- Length in bytes of the YAML front matter block at the start of a Markdown revision.
+ALTER TABLE request_revisions
+    ADD COLUMN front_matter_bytes INT NOT NULL DEFAULT 0;
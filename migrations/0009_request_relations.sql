@@ -0,0 +1,9 @@
+-- Each request has at most one parent, so relations form a tree per agent run.
+CREATE TABLE request_relations (
+    request_uuid UUID PRIMARY KEY REFERENCES requests(uuid) ON DELETE CASCADE,
+    parent_uuid UUID NOT NULL REFERENCES requests(uuid) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('subagent_of', 'continues', 'forked_from')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX request_relations_parent_idx ON request_relations (parent_uuid);
@@ -0,0 +1,13 @@
+-- Git commits declared by uploads, so a commit can be traced back to its transcript.
+CREATE TABLE request_commits (
+    request_uuid UUID NOT NULL REFERENCES requests(uuid) ON DELETE CASCADE,
+    sha TEXT NOT NULL,
+    rev INT NOT NULL,
+    repo_url TEXT,
+    branch TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (request_uuid, sha)
+);
+
+-- text_pattern_ops lets abbreviated SHAs use the index via LIKE 'prefix%'.
+CREATE INDEX request_commits_sha_idx ON request_commits (sha text_pattern_ops);
@@ -0,0 +1,6 @@
+-- SimHash fingerprint of each revision; requests keep the latest copy for duplicate lookups.
+ALTER TABLE request_revisions
+    ADD COLUMN simhash BIGINT;
+
+ALTER TABLE requests
+    ADD COLUMN simhash BIGINT;
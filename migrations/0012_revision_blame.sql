@@ -0,0 +1,10 @@
+-- Cached blame per revision: the revision that introduced each line, in line order.
+CREATE TABLE revision_blame (
+    request_uuid UUID NOT NULL,
+    rev_number INT NOT NULL,
+    line_revs INT[] NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (request_uuid, rev_number),
+    FOREIGN KEY (request_uuid, rev_number)
+        REFERENCES request_revisions (request_uuid, rev_number) ON DELETE CASCADE
+);
@@ -0,0 +1,6 @@
+-- Who may read a request through the public endpoints. Unlisted requests additionally
+-- require `share_token`, which is rotated to revoke old links.
+ALTER TABLE requests
+    ADD COLUMN visibility TEXT NOT NULL DEFAULT 'public'
+        CHECK (visibility IN ('public', 'unlisted', 'private')),
+    ADD COLUMN share_token TEXT;
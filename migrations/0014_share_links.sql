@@ -0,0 +1,12 @@
+-- Signed, expiring share links. The signature proves a link was minted by us; this table
+-- lets owners revoke one before it expires.
+CREATE TABLE share_links (
+    id UUID PRIMARY KEY,
+    request_uuid UUID NOT NULL REFERENCES requests(uuid) ON DELETE CASCADE,
+    rev INT,
+    expires_at TIMESTAMPTZ NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX share_links_request_idx ON share_links (request_uuid);
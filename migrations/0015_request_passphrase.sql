@@ -0,0 +1,3 @@
+-- Argon2 hash (PHC string) of the passphrase non-owners must present to read a request.
+ALTER TABLE requests
+    ADD COLUMN passphrase_hash TEXT;
@@ -0,0 +1,13 @@
+-- Remaining non-owner raw reads before a request burns or a share link is used up.
+-- NULL means unlimited.
+ALTER TABLE requests
+    ADD COLUMN views_remaining INT CHECK (views_remaining >= 0);
+
+ALTER TABLE share_links
+    ADD COLUMN views_remaining INT CHECK (views_remaining >= 0);
+
+-- Requests deleted after their last view, so later reads answer 410 instead of 404.
+CREATE TABLE burned_requests (
+    uuid UUID PRIMARY KEY,
+    burned_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
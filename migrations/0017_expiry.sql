@@ -0,0 +1,15 @@
+-- Optional expiry of whole requests and of single revisions. Expired rows stop being
+-- served at once and are deleted, with their objects, by the retention sweeper.
+ALTER TABLE requests
+    ADD COLUMN expires_at TIMESTAMPTZ;
+
+ALTER TABLE request_revisions
+    ADD COLUMN expires_at TIMESTAMPTZ;
+
+CREATE INDEX requests_expires_at_idx ON requests (expires_at) WHERE expires_at IS NOT NULL;
+CREATE INDEX request_revisions_expires_at_idx
+    ON request_revisions (expires_at) WHERE expires_at IS NOT NULL;
+
+-- Time-to-live applied to an account's new requests when the upload sets none.
+ALTER TABLE accounts
+    ADD COLUMN default_ttl_secs BIGINT CHECK (default_ttl_secs > 0);
@@ -0,0 +1,70 @@
+-- Named limit sets assigned to accounts by an admin.
+CREATE TABLE tiers (
+    name TEXT PRIMARY KEY,
+    max_total_bytes BIGINT NOT NULL CHECK (max_total_bytes > 0),
+    max_requests BIGINT NOT NULL CHECK (max_requests > 0),
+    max_upload_bytes BIGINT NOT NULL CHECK (max_upload_bytes > 0),
+    min_request_interval_ms BIGINT NOT NULL CHECK (min_request_interval_ms >= 0)
+);
+
+INSERT INTO tiers (name, max_total_bytes, max_requests, max_upload_bytes, min_request_interval_ms)
+VALUES
+    ('free', 1073741824, 10000, 1048576, 1000),
+    ('pro', 53687091200, 1000000, 16777216, 100);
+
+ALTER TABLE accounts
+    ADD COLUMN tier TEXT NOT NULL DEFAULT 'free' REFERENCES tiers(name);
+
+-- Size of each object in segment_keys, so deleting a revision can release exactly the
+-- bytes of the objects it frees.
+ALTER TABLE request_revisions
+    ADD COLUMN segment_bytes BIGINT[];
+
+-- A revision's content is the concatenation of its segments, and the revision that wrote
+-- a segment holds everything up to and including it. Segments whose writer was deleted
+-- are folded into the next known one.
+WITH segments AS (
+    SELECT rr.id, s.ord,
+           COALESCE(MAX(o.size_bytes::BIGINT) OVER (PARTITION BY rr.id ORDER BY s.ord), 0) AS through
+    FROM request_revisions rr
+    CROSS JOIN LATERAL unnest(rr.segment_keys) WITH ORDINALITY AS s(key, ord)
+    LEFT JOIN request_revisions o
+        ON o.request_uuid = rr.request_uuid AND o.object_key = s.key
+),
+steps AS (
+    SELECT id, ord,
+           through - COALESCE(LAG(through) OVER (PARTITION BY id ORDER BY ord), 0) AS bytes
+    FROM segments
+)
+UPDATE request_revisions rr
+SET segment_bytes = sizes.bytes
+FROM (SELECT id, array_agg(bytes ORDER BY ord) AS bytes FROM steps GROUP BY id) sizes
+WHERE sizes.id = rr.id;
+
+UPDATE request_revisions SET segment_bytes = '{}' WHERE segment_bytes IS NULL;
+
+ALTER TABLE request_revisions
+    ALTER COLUMN segment_bytes SET NOT NULL,
+    ALTER COLUMN segment_bytes SET DEFAULT '{}';
+
+-- Running totals checked against the account's tier, kept in step with uploads and deletes.
+CREATE TABLE account_usage (
+    account_id BIGINT PRIMARY KEY REFERENCES accounts(id) ON DELETE CASCADE,
+    total_bytes BIGINT NOT NULL DEFAULT 0,
+    request_count BIGINT NOT NULL DEFAULT 0
+);
+
+INSERT INTO account_usage (account_id, total_bytes, request_count)
+SELECT a.id,
+       COALESCE((
+           SELECT SUM(s.bytes)
+           FROM requests r
+           CROSS JOIN LATERAL (
+               SELECT DISTINCT ON (seg.key) seg.bytes
+               FROM request_revisions rr, unnest(rr.segment_keys, rr.segment_bytes) AS seg(key, bytes)
+               WHERE rr.request_uuid = r.uuid
+           ) s
+           WHERE r.account_id = a.id
+       ), 0),
+       (SELECT COUNT(*) FROM requests r WHERE r.account_id = a.id)
+FROM accounts a;
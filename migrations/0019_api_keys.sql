@@ -0,0 +1,20 @@
+-- Accounts can hold several named keys, each revocable on its own. The hash that used to
+-- live on the account becomes its first key, so existing keys keep working.
+CREATE TABLE api_keys (
+    id UUID PRIMARY KEY,
+    account_id BIGINT NOT NULL REFERENCES accounts(id) ON DELETE CASCADE,
+    key_hash TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_used_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ
+);
+
+CREATE INDEX api_keys_account_idx ON api_keys (account_id);
+
+INSERT INTO api_keys (id, account_id, key_hash, name, created_at, last_used_at)
+SELECT gen_random_uuid(), id, api_key_hash, 'default', created_at, last_used_at
+FROM accounts;
+
+ALTER TABLE accounts DROP COLUMN api_key_hash;
@@ -0,0 +1,6 @@
+-- What each key may do. Existing keys keep full access; request_uuids NULL means the key
+-- is not limited to particular requests.
+ALTER TABLE api_keys
+    ADD COLUMN scopes TEXT[] NOT NULL
+        DEFAULT ARRAY['requests:create', 'requests:update', 'requests:read', 'requests:delete'],
+    ADD COLUMN request_uuids UUID[];
@@ -0,0 +1,6 @@
+-- Requests created before 0004 were never indexed. NULL marks a request whose search text
+-- has not been computed yet; the server fills these in from storage at startup.
+ALTER TABLE requests
+    ALTER COLUMN search_text DROP NOT NULL;
+
+UPDATE requests SET search_text = NULL WHERE search_text = '';
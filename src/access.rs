@@ -0,0 +1,704 @@
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Query},
+    http::{
+        header::{AUTHORIZATION, COOKIE},
+        request::Parts,
+        HeaderMap,
+    },
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use hmac::Mac;
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    auth::{AuthContext, ClientIp},
+    error::ApiError,
+    passphrase::{self, PASSPHRASE_HEADER},
+    scopes::Scope,
+    util::{generate_share_token, hmac_sha256},
+    AppState,
+};
+
+pub const VISIBILITY_HEADER: &str = "x-prompt-visibility";
+pub const MAX_VIEWS_HEADER: &str = "x-prompt-max-views";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+    /// Anyone who knows the UUID.
+    #[default]
+    Public,
+    /// Anyone with the UUID and the request's current share token.
+    Unlisted,
+    /// Only the owning account.
+    Private,
+}
+
+impl Visibility {
+    pub fn parse(raw: &str) -> Result<Self, ApiError> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "public" => Ok(Visibility::Public),
+            "unlisted" => Ok(Visibility::Unlisted),
+            "private" => Ok(Visibility::Private),
+            other => Err(ApiError::BadRequest(format!(
+                "visibility must be public, unlisted or private, got {other:?}"
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Public => "public",
+            Visibility::Unlisted => "unlisted",
+            Visibility::Private => "private",
+        }
+    }
+
+    /// Reads `X-Prompt-Visibility`, if present.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, ApiError> {
+        headers
+            .get(VISIBILITY_HEADER)
+            .map(|value| {
+                value
+                    .to_str()
+                    .map_err(|_| {
+                        ApiError::BadRequest(format!("invalid {VISIBILITY_HEADER} header"))
+                    })
+                    .and_then(Visibility::parse)
+            })
+            .transpose()
+    }
+
+    /// The share token a request gets when created with this visibility.
+    pub fn initial_token(self) -> Option<String> {
+        (self == Visibility::Unlisted).then(generate_share_token)
+    }
+}
+
+/// Changes a request's visibility. Making a request unlisted keeps its current token (or
+/// issues one); any other visibility drops the token so old links stop working. Returns the
+/// token now in effect.
+pub async fn set_visibility(
+    tx: &mut Transaction<'_, Postgres>,
+    uuid: Uuid,
+    account_id: i64,
+    visibility: Visibility,
+) -> Result<Option<String>, ApiError> {
+    let token: Option<Option<String>> = sqlx::query_scalar(
+        "UPDATE requests SET visibility = $3, \
+             share_token = CASE WHEN $3 = 'unlisted' THEN COALESCE(share_token, $4) END \
+         WHERE uuid = $1 AND account_id = $2 \
+         RETURNING share_token",
+    )
+    .bind(uuid)
+    .bind(account_id)
+    .bind(visibility.as_str())
+    .bind(generate_share_token())
+    .fetch_optional(&mut **tx)
+    .await?;
+    token.ok_or(ApiError::NotFound)
+}
+
+/// A signed share link: `?link=<id>&expires=<unix secs>&sig=<signature>`, plus `rev=` when
+/// the link is pinned to one revision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SignedLink {
+    pub id: Uuid,
+    pub rev: Option<i32>,
+    pub expires: i64,
+    pub sig: String,
+}
+
+impl SignedLink {
+    pub fn new(
+        secret: &str,
+        uuid: Uuid,
+        id: Uuid,
+        rev: Option<i32>,
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        let expires = expires_at.timestamp();
+        let message = link_message(uuid, id, rev, expires);
+        let sig = hmac_sha256(secret.as_bytes(), &[message.as_bytes()]).finalize();
+        let sig = URL_SAFE_NO_PAD.encode(sig.into_bytes());
+        Self {
+            id,
+            rev,
+            expires,
+            sig,
+        }
+    }
+
+    /// The query string that carries the link, without the leading `?`.
+    pub fn query(&self) -> String {
+        let rev = self
+            .rev
+            .map(|rev| format!("rev={rev}&"))
+            .unwrap_or_default();
+        format!(
+            "{rev}link={}&expires={}&sig={}",
+            self.id, self.expires, self.sig
+        )
+    }
+
+    pub fn verify(&self, secret: &str, uuid: Uuid) -> bool {
+        let Ok(sig) = URL_SAFE_NO_PAD.decode(&self.sig) else {
+            return false;
+        };
+        let message = link_message(uuid, self.id, self.rev, self.expires);
+        hmac_sha256(secret.as_bytes(), &[message.as_bytes()])
+            .verify_slice(&sig)
+            .is_ok()
+    }
+}
+
+/// What a share link's signature covers.
+fn link_message(uuid: Uuid, id: Uuid, rev: Option<i32>, expires: i64) -> String {
+    let rev = rev.map(|rev| rev.to_string()).unwrap_or_default();
+    format!("{uuid}:{id}:{rev}:{expires}")
+}
+
+/// Validates a view limit for a request or share link.
+pub fn validate_max_views(views: i32) -> Result<i32, ApiError> {
+    if views < 1 {
+        return Err(ApiError::BadRequest(
+            "max_views must be at least 1".to_string(),
+        ));
+    }
+    Ok(views)
+}
+
+/// Reads `X-Prompt-Max-Views`, if present.
+pub fn max_views_from_headers(headers: &HeaderMap) -> Result<Option<i32>, ApiError> {
+    headers
+        .get(MAX_VIEWS_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .ok_or_else(|| ApiError::BadRequest("invalid max views header".to_string()))
+                .and_then(validate_max_views)
+        })
+        .transpose()
+}
+
+#[derive(Deserialize)]
+struct AccessQuery {
+    token: Option<String>,
+    rev: Option<i32>,
+    link: Option<Uuid>,
+    expires: Option<i64>,
+    sig: Option<String>,
+}
+
+/// The reader of a public endpoint: the caller's key when an `Authorization` header is sent,
+/// the share token from `?token=`, a signed share link and any passphrase proof (the
+/// `X-Prompt-Passphrase` header or an unlock cookie).
+#[derive(Clone, Debug, Default)]
+pub struct Viewer {
+    pub auth: Option<AuthContext>,
+    pub token: Option<String>,
+    pub link: Option<SignedLink>,
+    pub passphrase: Option<String>,
+    pub cookies: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct AccessRow {
+    account_id: i64,
+    visibility: String,
+    share_token: Option<String>,
+    passphrase_hash: Option<String>,
+    views_remaining: Option<i32>,
+}
+
+/// A successful authorization. Non-owner raw reads of view-limited requests or share links
+/// must be counted with [`Grant::count_view`].
+#[must_use]
+#[derive(Debug)]
+pub struct Grant {
+    uuid: Uuid,
+    owner: bool,
+    request_limited: bool,
+    /// The share link used, if it has a view limit.
+    limited_link: Option<Uuid>,
+}
+
+impl Grant {
+    pub fn is_view_limited(&self) -> bool {
+        !self.owner && (self.request_limited || self.limited_link.is_some())
+    }
+
+    /// Takes one view off the share link and the request. Concurrent readers race on the
+    /// decrement, so exactly one of them gets the last view; the others get `410`. Returns
+    /// true when this was the request's last view and it should now be burned.
+    pub async fn count_view(&self, state: &AppState) -> Result<bool, ApiError> {
+        if !self.is_view_limited() {
+            return Ok(false);
+        }
+        let mut tx = state.pool.begin().await?;
+
+        if let Some(id) = self.limited_link {
+            sqlx::query_scalar::<_, i32>(
+                "UPDATE share_links SET views_remaining = views_remaining - 1 \
+                 WHERE id = $1 AND views_remaining > 0 \
+                 RETURNING views_remaining",
+            )
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(link_used_up)?;
+        }
+
+        let mut burned = false;
+        if self.request_limited {
+            let left: i32 = sqlx::query_scalar(
+                "UPDATE requests SET views_remaining = views_remaining - 1 \
+                 WHERE uuid = $1 AND views_remaining > 0 \
+                 RETURNING views_remaining",
+            )
+            .bind(self.uuid)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(burned_request)?;
+            if left == 0 {
+                sqlx::query(
+                    "INSERT INTO burned_requests (uuid) VALUES ($1) ON CONFLICT DO NOTHING",
+                )
+                .bind(self.uuid)
+                .execute(&mut *tx)
+                .await?;
+                burned = true;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(burned)
+    }
+}
+
+/// Access held by a connected reader, from [`Viewer::authorize_standing`].
+#[derive(Clone, Debug)]
+pub struct StandingAccess {
+    /// The passphrase hash the reader was admitted under, if any.
+    passphrase_hash: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Viewer {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let auth = if parts.headers.contains_key(AUTHORIZATION) {
+            Some(AuthContext::from_request_parts(parts, state).await?)
+        } else {
+            None
+        };
+        let Query(q) = Query::<AccessQuery>::try_from_uri(&parts.uri)
+            .map_err(|e| ApiError::BadRequest(e.body_text()))?;
+        let link = match (q.link, q.expires, q.sig) {
+            (None, None, None) => None,
+            (Some(id), Some(expires), Some(sig)) => Some(SignedLink {
+                id,
+                rev: q.rev,
+                expires,
+                sig,
+            }),
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "share links need link, expires and sig".to_string(),
+                ))
+            }
+        };
+        let ip = ClientIp::from_request_parts(parts, state).await.ok();
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        Ok(Viewer {
+            auth,
+            token: q.token.filter(|t| !t.is_empty()),
+            link,
+            passphrase: header(PASSPHRASE_HEADER),
+            cookies: header(COOKIE.as_str()),
+            ip: ip.map(|ClientIp(ip)| ip.to_string()),
+        })
+    }
+}
+
+impl Viewer {
+    /// The caller's account, if its key may read `uuid` as the owner. A key without
+    /// `requests:read` on the request reads it like anyone else.
+    pub fn owner_account(&self, uuid: Uuid) -> Option<i64> {
+        self.auth
+            .as_ref()
+            .filter(|auth| auth.scopes.require(Scope::Read, uuid).is_ok())
+            .map(|auth| auth.account_id)
+    }
+
+    /// The caller's account, if its key may read all of the account's requests.
+    pub fn reader_account(&self) -> Option<i64> {
+        self.auth
+            .as_ref()
+            .filter(|auth| auth.scopes.require_all(Scope::Read).is_ok())
+            .map(|auth| auth.account_id)
+    }
+
+    /// Fails with `404` unless this viewer may read `uuid`, so private and unlisted requests
+    /// are indistinguishable from missing ones. Share links pinned to a revision are refused
+    /// here; only [`Viewer::authorize_revision`] accepts them. So are non-owner reads of
+    /// view-limited requests and links, which are only counted by raw reads.
+    pub async fn authorize(&self, state: &AppState, uuid: Uuid) -> Result<(), ApiError> {
+        self.authorize_standing(state, uuid).await.map(|_| ())
+    }
+
+    /// Like [`Viewer::authorize`], for readers that stay connected. The result lets
+    /// [`Viewer::recheck`] confirm later that access still holds.
+    pub async fn authorize_standing(
+        &self,
+        state: &AppState,
+        uuid: Uuid,
+    ) -> Result<StandingAccess, ApiError> {
+        if self.link.as_ref().is_some_and(|link| link.rev.is_some()) {
+            return Err(ApiError::NotFound);
+        }
+        let (grant, passphrase_hash) = self.admit_with_passphrase(state, uuid).await?;
+        if grant.is_view_limited() {
+            return Err(view_limited());
+        }
+        Ok(StandingAccess { passphrase_hash })
+    }
+
+    /// Checks that access granted by [`Viewer::authorize_standing`] still holds: the request
+    /// is still there and readable by this viewer, has not gained a view limit, and its
+    /// passphrase is the one proven then, so the passphrase is not verified again.
+    pub async fn recheck(
+        &self,
+        state: &AppState,
+        uuid: Uuid,
+        access: &StandingAccess,
+    ) -> Result<(), ApiError> {
+        let (row, grant) = self.admit(state, uuid).await?;
+        if grant.owner {
+            return Ok(());
+        }
+        if grant.is_view_limited() {
+            return Err(view_limited());
+        }
+        if row.passphrase_hash != access.passphrase_hash {
+            return Err(ApiError::PassphraseRequired(
+                "the passphrase of this request has changed".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Like [`Viewer::authorize`], for reads of the single revision named by `?rev=`.
+    /// A share link replaces the visibility check: it must carry a valid signature (`404`
+    /// otherwise) and be unexpired, unrevoked and not used up (`410` otherwise). Non-owners
+    /// must then also prove the passphrase, if the request has one (`401` otherwise).
+    pub async fn authorize_revision(
+        &self,
+        state: &AppState,
+        uuid: Uuid,
+    ) -> Result<Grant, ApiError> {
+        self.admit_with_passphrase(state, uuid)
+            .await
+            .map(|(grant, _)| grant)
+    }
+
+    /// [`Viewer::admit`] plus the passphrase check for non-owners. Also returns the
+    /// request's passphrase hash.
+    async fn admit_with_passphrase(
+        &self,
+        state: &AppState,
+        uuid: Uuid,
+    ) -> Result<(Grant, Option<String>), ApiError> {
+        let (row, grant) = self.admit(state, uuid).await?;
+        if grant.owner {
+            return Ok((grant, row.passphrase_hash));
+        }
+        let Some(hash) = row.passphrase_hash else {
+            return Ok((grant, None));
+        };
+
+        let cookie = self
+            .cookies
+            .as_deref()
+            .and_then(|cookies| passphrase::find_cookie(cookies, &passphrase::cookie_name(uuid)));
+        if cookie.is_some_and(|cookie| {
+            passphrase::verify_cookie(
+                &state.share_link_secret,
+                uuid,
+                &hash,
+                cookie,
+                Utc::now().timestamp(),
+            )
+        }) {
+            return Ok((grant, Some(hash)));
+        }
+        match &self.passphrase {
+            Some(given) => self.verify_passphrase(state, &hash, given).await?,
+            None => {
+                return Err(ApiError::PassphraseRequired(
+                    "this request is protected by a passphrase".to_string(),
+                ))
+            }
+        }
+        Ok((grant, Some(hash)))
+    }
+
+    /// Checks `given` against the request's passphrase and returns the unlock cookie value
+    /// that stands in for it on later reads.
+    pub async fn unlock(
+        &self,
+        state: &AppState,
+        uuid: Uuid,
+        given: &str,
+    ) -> Result<String, ApiError> {
+        let (row, _) = self.admit(state, uuid).await?;
+        let hash = row.passphrase_hash.ok_or_else(|| {
+            ApiError::BadRequest("request is not protected by a passphrase".to_string())
+        })?;
+        self.verify_passphrase(state, &hash, given).await?;
+        let expires = Utc::now().timestamp() + passphrase::UNLOCK_COOKIE_MAX_AGE_SECS as i64;
+        Ok(passphrase::cookie_value(
+            &state.share_link_secret,
+            uuid,
+            &hash,
+            expires,
+        ))
+    }
+
+    /// Loads the request's access settings and applies the ownership, share link and
+    /// visibility rules. Burned requests answer `410`, even to their owner.
+    async fn admit(&self, state: &AppState, uuid: Uuid) -> Result<(AccessRow, Grant), ApiError> {
+        let row = sqlx::query_as::<_, AccessRow>(
+            "SELECT account_id, visibility, share_token, passphrase_hash, views_remaining \
+             FROM requests WHERE uuid = $1 AND (expires_at IS NULL OR expires_at > now())",
+        )
+        .bind(uuid)
+        .fetch_optional(&state.pool)
+        .await?;
+        let Some(row) = row else {
+            let burned: Option<i32> =
+                sqlx::query_scalar("SELECT 1 FROM burned_requests WHERE uuid = $1")
+                    .bind(uuid)
+                    .fetch_optional(&state.pool)
+                    .await?;
+            return Err(burned.map_or(ApiError::NotFound, |_| burned_request()));
+        };
+        if row.views_remaining == Some(0) {
+            return Err(burned_request());
+        }
+
+        let mut grant = Grant {
+            uuid,
+            owner: self.owner_account(uuid) == Some(row.account_id),
+            request_limited: row.views_remaining.is_some(),
+            limited_link: None,
+        };
+        if grant.owner {
+            return Ok((row, grant));
+        }
+        if let Some(link) = &self.link {
+            if check_link(state, uuid, link).await?.is_some() {
+                grant.limited_link = Some(link.id);
+            }
+            return Ok((row, grant));
+        }
+        match Visibility::parse(&row.visibility)? {
+            Visibility::Public => Ok((row, grant)),
+            Visibility::Unlisted => match (&self.token, &row.share_token) {
+                (Some(given), Some(expected)) if tokens_match(given, expected) => Ok((row, grant)),
+                _ => Err(ApiError::NotFound),
+            },
+            Visibility::Private => Err(ApiError::NotFound),
+        }
+    }
+
+    /// Each attempt locks the client IP out of further ones for a few seconds, separately
+    /// from the public read limit. The attempt is recorded before the slow Argon2 check, so
+    /// parallel guesses cannot all slip through, and only a few checks run at once.
+    async fn verify_passphrase(
+        &self,
+        state: &AppState,
+        hash: &str,
+        given: &str,
+    ) -> Result<(), ApiError> {
+        let key = self.ip.as_deref().unwrap_or("unknown");
+        state.passphrase_limiter.check(key)?;
+        let _permit =
+            state
+                .passphrase_permits
+                .try_acquire()
+                .map_err(|_| ApiError::RateLimited {
+                    retry_after_secs: 1,
+                })?;
+        if passphrase::verify(hash.to_string(), given.to_string()).await? {
+            return Ok(());
+        }
+        Err(ApiError::PassphraseRequired(
+            "incorrect passphrase".to_string(),
+        ))
+    }
+}
+
+/// Checks a share link and returns its remaining views, if it is view-limited.
+async fn check_link(
+    state: &AppState,
+    uuid: Uuid,
+    link: &SignedLink,
+) -> Result<Option<i32>, ApiError> {
+    if !link.verify(&state.share_link_secret, uuid) {
+        return Err(ApiError::NotFound);
+    }
+    if link.expires <= Utc::now().timestamp() {
+        return Err(ApiError::Gone("share link has expired".to_string()));
+    }
+    let (revoked, views_remaining): (Option<DateTime<Utc>>, Option<i32>) = sqlx::query_as(
+        "SELECT revoked_at, views_remaining FROM share_links \
+         WHERE id = $1 AND request_uuid = $2",
+    )
+    .bind(link.id)
+    .bind(uuid)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    if revoked.is_some() {
+        return Err(ApiError::Gone("share link has been revoked".to_string()));
+    }
+    if views_remaining == Some(0) {
+        return Err(link_used_up());
+    }
+    Ok(views_remaining)
+}
+
+fn view_limited() -> ApiError {
+    ApiError::BadRequest("view-limited requests can only be read with GET /:uuid".to_string())
+}
+
+fn burned_request() -> ApiError {
+    ApiError::Gone("request was deleted after its last view".to_string())
+}
+
+fn link_used_up() -> ApiError {
+    ApiError::Gone("share link has no views left".to_string())
+}
+
+/// Constant-time comparison so response timing does not reveal a token prefix.
+pub(crate) fn tokens_match(given: &str, expected: &str) -> bool {
+    let (a, b) = (given.as_bytes(), expected.as_bytes());
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scopes::Scopes;
+
+    #[test]
+    fn parses_visibility() {
+        assert_eq!(Visibility::parse("Unlisted").unwrap(), Visibility::Unlisted);
+        assert_eq!(Visibility::parse(" private ").unwrap(), Visibility::Private);
+        assert!(Visibility::parse("secret").is_err());
+        assert!(Visibility::Public.initial_token().is_none());
+        assert!(Visibility::Unlisted.initial_token().is_some());
+    }
+
+    #[test]
+    fn signed_links_cover_every_field() {
+        let uuid = Uuid::new_v4();
+        let id = Uuid::new_v4();
+        let expires_at = Utc::now();
+        let link = SignedLink::new("secret", uuid, id, Some(2), expires_at);
+        assert!(link.verify("secret", uuid));
+        assert!(link.query().starts_with("rev=2&link="));
+
+        assert!(!link.verify("other", uuid));
+        assert!(!link.verify("secret", Uuid::new_v4()));
+        assert!(!SignedLink {
+            rev: Some(3),
+            ..link.clone()
+        }
+        .verify("secret", uuid));
+        assert!(!SignedLink {
+            rev: None,
+            ..link.clone()
+        }
+        .verify("secret", uuid));
+        assert!(!SignedLink {
+            expires: link.expires + 1,
+            ..link.clone()
+        }
+        .verify("secret", uuid));
+        assert!(!SignedLink {
+            sig: "%%".to_string(),
+            ..link
+        }
+        .verify("secret", uuid));
+    }
+
+    #[test]
+    fn parses_max_views_header() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(max_views_from_headers(&headers).unwrap(), None);
+        headers.insert(MAX_VIEWS_HEADER, " 3 ".parse().unwrap());
+        assert_eq!(max_views_from_headers(&headers).unwrap(), Some(3));
+        headers.insert(MAX_VIEWS_HEADER, "0".parse().unwrap());
+        assert!(max_views_from_headers(&headers).is_err());
+        headers.insert(MAX_VIEWS_HEADER, "many".parse().unwrap());
+        assert!(max_views_from_headers(&headers).is_err());
+    }
+
+    #[test]
+    fn only_keys_that_can_read_count_as_the_owner() {
+        let uuid = Uuid::new_v4();
+        let viewer = |scopes| Viewer {
+            auth: Some(AuthContext {
+                account_id: 7,
+                key_id: Uuid::new_v4(),
+                scopes,
+                tier: crate::models::Tier {
+                    name: "free".to_string(),
+                    max_total_bytes: 1,
+                    max_requests: 1,
+                    max_upload_bytes: 1,
+                    min_request_interval_ms: 0,
+                },
+                via_token: false,
+            }),
+            ..Viewer::default()
+        };
+
+        let create_only = viewer(Scopes::new(vec![Scope::Create], None));
+        assert_eq!(create_only.owner_account(uuid), None);
+        assert_eq!(create_only.reader_account(), None);
+
+        let one = viewer(Scopes::new(vec![Scope::Read], Some(vec![uuid])));
+        assert_eq!(one.owner_account(uuid), Some(7));
+        assert_eq!(one.owner_account(Uuid::new_v4()), None);
+        assert_eq!(one.reader_account(), None);
+
+        assert_eq!(viewer(Scopes::full()).reader_account(), Some(7));
+        assert_eq!(Viewer::default().owner_account(uuid), None);
+    }
+
+    #[test]
+    fn compares_tokens() {
+        assert!(tokens_match("abc", "abc"));
+        assert!(!tokens_match("abd", "abc"));
+        assert!(!tokens_match("ab", "abc"));
+    }
+}
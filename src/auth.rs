@@ -3,13 +3,46 @@ use axum::{
     extract::{FromRequestParts, State},
     http::{header::AUTHORIZATION, request::Parts},
 };
-use std::net::{IpAddr, SocketAddr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+use uuid::Uuid;
 
-use crate::{error::ApiError, util::hash_api_key, AppState};
+use crate::{
+    access::tokens_match,
+    error::ApiError,
+    models::Tier,
+    quota::{self, TIER_COLUMNS},
+    scopes::Scopes,
+    tokens::{UploadToken, TOKEN_PREFIX},
+    AppState,
+};
 
 #[derive(Clone, Debug)]
 pub struct AuthContext {
     pub account_id: i64,
+    /// The API key this call was made with, or that minted its upload token.
+    pub key_id: Uuid,
+    /// What the key may do; handlers check it before touching requests.
+    pub scopes: Scopes,
+    /// The account's limits, loaded with the key.
+    pub tier: Tier,
+    /// Whether the call was made with an upload token rather than the key itself.
+    pub via_token: bool,
+}
+
+impl AuthContext {
+    /// Managing keys, tokens and account settings takes a full-access API key. Upload
+    /// tokens never qualify, whatever their scopes.
+    pub fn require_full_key(&self) -> Result<(), ApiError> {
+        if self.via_token {
+            return Err(ApiError::Forbidden(
+                "upload tokens cannot do this; use an API key".to_string(),
+            ));
+        }
+        self.scopes.require_full()
+    }
 }
 
 #[async_trait]
@@ -24,42 +57,126 @@ impl FromRequestParts<AppState> for AuthContext {
             .await
             .map_err(|_| ApiError::Internal("state unavailable".to_string()))?;
 
-        let header = parts
-            .headers
-            .get(AUTHORIZATION)
-            .ok_or(ApiError::Unauthorized)?;
-        let header = header.to_str().map_err(|_| ApiError::Unauthorized)?;
-        let key = header
-            .strip_prefix("Bearer ")
-            .ok_or(ApiError::Unauthorized)?;
-        if key.trim().is_empty() {
-            return Err(ApiError::Unauthorized);
+        let key = bearer_token(parts)?;
+        if key.starts_with(TOKEN_PREFIX) {
+            return from_upload_token(key, &app).await;
         }
 
-        let hash = hash_api_key(key, app.api_key_pepper.as_deref());
         #[derive(sqlx::FromRow)]
-        struct AccountRow {
-            id: i64,
+        struct KeyRow {
+            key_id: Uuid,
+            key_hash: String,
+            account_id: i64,
+            scopes: Vec<String>,
+            request_uuids: Option<Vec<Uuid>>,
+            #[sqlx(flatten)]
+            tier: Tier,
         }
 
-        let row = sqlx::query_as::<_, AccountRow>(
-            r#"SELECT id FROM accounts WHERE api_key_hash = $1"#,
-        )
-        .bind(hash)
+        let row = sqlx::query_as::<_, KeyRow>(&format!(
+            "SELECT k.id AS key_id, k.key_hash, k.account_id, k.scopes, k.request_uuids, \
+                    {TIER_COLUMNS} \
+             FROM api_keys k \
+             JOIN accounts a ON a.id = k.account_id \
+             JOIN tiers t ON t.name = a.tier \
+             WHERE k.key_hash = ANY($1) AND k.revoked_at IS NULL \
+               AND (k.expires_at IS NULL OR k.expires_at > now())"
+        ))
+        .bind(app.key_hasher.candidates(key))
         .fetch_optional(&app.pool)
         .await
         .map_err(ApiError::from)?
         .ok_or(ApiError::Unauthorized)?;
 
-        app.account_limiter.check(&row.id.to_string())?;
+        app.account_limiter.check_within(
+            &row.account_id.to_string(),
+            Duration::from_millis(row.tier.min_request_interval_ms.max(0) as u64),
+        )?;
 
-        let _ = sqlx::query(r#"UPDATE accounts SET last_used_at = now() WHERE id = $1"#)
-            .bind(row.id)
-            .execute(&app.pool)
-            .await;
+        // Keys found under an older pepper or hash format move to the newest one.
+        let hash = app.key_hasher.hash(key);
+        let rehash = (row.key_hash != hash).then_some(hash);
+        let _ = sqlx::query(
+            r#"WITH k AS (
+                   UPDATE api_keys
+                   SET last_used_at = now(), key_hash = COALESCE($2, key_hash)
+                   WHERE id = $1
+                   RETURNING account_id
+               )
+               UPDATE accounts SET last_used_at = now() FROM k WHERE accounts.id = k.account_id"#,
+        )
+        .bind(row.key_id)
+        .bind(rehash)
+        .execute(&app.pool)
+        .await;
+
+        Ok(AuthContext {
+            account_id: row.account_id,
+            key_id: row.key_id,
+            scopes: Scopes::from_names(&row.scopes, row.request_uuids),
+            tier: row.tier,
+            via_token: false,
+        })
+    }
+}
+
+/// Upload tokens carry their own account and scopes, so only the account's tier is loaded.
+/// They are not tracked in `last_used_at` and stay valid until they expire, even if the
+/// key that minted them is revoked.
+async fn from_upload_token(token: &str, app: &AppState) -> Result<AuthContext, ApiError> {
+    let token = UploadToken::verify(token, &app.token_signing_key, chrono::Utc::now())?;
+    let tier = quota::account_tier(&app.pool, token.account_id)
+        .await?
+        .ok_or(ApiError::Unauthorized)?;
+
+    app.account_limiter.check_within(
+        &token.account_id.to_string(),
+        Duration::from_millis(tier.min_request_interval_ms.max(0) as u64),
+    )?;
+
+    Ok(AuthContext {
+        account_id: token.account_id,
+        key_id: token.key_id,
+        scopes: token.scopes(),
+        tier,
+        via_token: true,
+    })
+}
 
-        Ok(AuthContext { account_id: row.id })
+/// Operator access to the admin API, authorized by `ADMIN_TOKEN`. The admin API does not
+/// exist when no token is configured.
+#[derive(Clone, Copy, Debug)]
+pub struct AdminAuth;
+
+#[async_trait]
+impl FromRequestParts<AppState> for AdminAuth {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let expected = state.admin_token.as_deref().ok_or(ApiError::NotFound)?;
+        if !tokens_match(bearer_token(parts)?, expected) {
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(AdminAuth)
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Result<&str, ApiError> {
+    let header = parts
+        .headers
+        .get(AUTHORIZATION)
+        .ok_or(ApiError::Unauthorized)?;
+    let header = header.to_str().map_err(|_| ApiError::Unauthorized)?;
+    let token = header
+        .strip_prefix("Bearer ")
+        .ok_or(ApiError::Unauthorized)?;
+    if token.trim().is_empty() {
+        return Err(ApiError::Unauthorized);
     }
+    Ok(token)
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -83,8 +200,9 @@ where
             }
         }
 
-        if let Some(axum::extract::ConnectInfo(addr)) =
-            parts.extensions.get::<axum::extract::ConnectInfo<SocketAddr>>()
+        if let Some(axum::extract::ConnectInfo(addr)) = parts
+            .extensions
+            .get::<axum::extract::ConnectInfo<SocketAddr>>()
         {
             return Ok(ClientIp(addr.ip()));
         }
@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    models::{BlameLine, BlameResponse},
+    revisions, AppState,
+};
+
+/// Carries line origins from one revision to the next: lines the diff keeps retain their
+/// origin, inserted or replaced lines are attributed to `rev`.
+pub fn advance(old: &[&str], origins: &[i32], new: &[&str], rev: i32) -> Vec<i32> {
+    let mut out = vec![rev; new.len()];
+    for op in capture_diff_slices(Algorithm::Myers, old, new) {
+        if let DiffOp::Equal {
+            old_index,
+            new_index,
+            len,
+        } = op
+        {
+            out[new_index..new_index + len].copy_from_slice(&origins[old_index..old_index + len]);
+        }
+    }
+    out
+}
+
+/// Blame for `rev` (the latest revision when `None`). Starts from the closest cached
+/// revision at or below `rev` and walks the chain forward, caching every revision it passes.
+/// Expired revisions are skipped, so their lines are attributed to the surviving ones.
+pub async fn compute(
+    state: &AppState,
+    uuid: Uuid,
+    rev: Option<i32>,
+) -> Result<BlameResponse, ApiError> {
+    let target = revisions::fetch(state, uuid, rev).await?;
+
+    let chain: Vec<(i32, DateTime<Utc>)> = sqlx::query_as(
+        "SELECT rev_number, created_at FROM request_revisions \
+         WHERE request_uuid = $1 AND rev_number <= $2 \
+           AND (expires_at IS NULL OR expires_at > now()) \
+         ORDER BY rev_number",
+    )
+    .bind(uuid)
+    .bind(target.rev)
+    .fetch_all(&state.pool)
+    .await?;
+
+    let cached: Option<(i32, Vec<i32>)> = sqlx::query_as(
+        "SELECT b.rev_number, b.line_revs FROM revision_blame b \
+         JOIN request_revisions rr \
+           ON rr.request_uuid = b.request_uuid AND rr.rev_number = b.rev_number \
+         WHERE b.request_uuid = $1 AND b.rev_number <= $2 \
+           AND (rr.expires_at IS NULL OR rr.expires_at > now()) \
+           AND NOT EXISTS ( \
+               SELECT 1 FROM request_revisions e \
+               WHERE e.request_uuid = $1 AND e.rev_number = ANY(b.line_revs) \
+                 AND e.expires_at <= now() \
+           ) \
+         ORDER BY b.rev_number DESC LIMIT 1",
+    )
+    .bind(uuid)
+    .bind(target.rev)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    let target_text = target.text();
+    let target_lines: Vec<&str> = target_text.lines().collect();
+
+    let origins = match cached {
+        Some((rev, origins)) if rev == target.rev && origins.len() == target_lines.len() => origins,
+        cached => {
+            let (base_rev, base_origins) = match cached {
+                Some((rev, origins)) if rev < target.rev => (rev, Some(origins)),
+                _ => (chain[0].0, None),
+            };
+            let base = if base_rev == target.rev {
+                None
+            } else {
+                Some(revisions::fetch(state, uuid, Some(base_rev)).await?)
+            };
+            let mut prev_text = base
+                .as_ref()
+                .map_or_else(|| target_text.clone(), |b| b.text());
+            let mut origins = match base_origins {
+                Some(origins) if origins.len() == prev_text.lines().count() => origins,
+                _ => {
+                    let origins = vec![base_rev; prev_text.lines().count()];
+                    store(state, uuid, base_rev, base_rev, &origins).await?;
+                    origins
+                }
+            };
+
+            let mut prev_rev = base_rev;
+            for &(rev, _) in chain.iter().filter(|(rev, _)| *rev > base_rev) {
+                let text = if rev == target.rev {
+                    target_text.clone()
+                } else {
+                    revisions::fetch(state, uuid, Some(rev)).await?.text()
+                };
+                let old: Vec<&str> = prev_text.lines().collect();
+                let new: Vec<&str> = text.lines().collect();
+                origins = advance(&old, &origins, &new, rev);
+                store(state, uuid, rev, prev_rev, &origins).await?;
+                prev_text = text;
+                prev_rev = rev;
+            }
+            origins
+        }
+    };
+
+    let created: HashMap<i32, DateTime<Utc>> = chain.into_iter().collect();
+    let lines = target_lines
+        .iter()
+        .zip(&origins)
+        .enumerate()
+        .map(|(idx, (text, rev))| BlameLine {
+            line: idx + 1,
+            rev: *rev,
+            created_at: created.get(rev).copied(),
+            text: text.to_string(),
+        })
+        .collect();
+
+    Ok(BlameResponse {
+        uuid,
+        rev: target.rev,
+        content_type: target.content_type,
+        lines,
+    })
+}
+
+/// Caches blame for `rev`, replacing a stale entry whose line count no longer matches,
+/// unless `base_rev` (the revision it was derived from) has been deleted in the meantime;
+/// deleting a revision drops the cache for everything after it.
+async fn store(
+    state: &AppState,
+    uuid: Uuid,
+    rev: i32,
+    base_rev: i32,
+    origins: &[i32],
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "INSERT INTO revision_blame (request_uuid, rev_number, line_revs) \
+         SELECT $1, $2, $3 \
+         WHERE EXISTS ( \
+             SELECT 1 FROM request_revisions WHERE request_uuid = $1 AND rev_number = $4 \
+         ) \
+         ON CONFLICT (request_uuid, rev_number) DO UPDATE SET line_revs = EXCLUDED.line_revs",
+    )
+    .bind(uuid)
+    .bind(rev)
+    .bind(origins)
+    .bind(base_rev)
+    .execute(&state.pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_keeps_origins_of_unchanged_lines() {
+        let v1 = ["# Plan", "step one", "step two"];
+        let origins = vec![1; v1.len()];
+
+        let v2 = ["# Plan", "step one", "step 2", "step three"];
+        let origins = advance(&v1, &origins, &v2, 2);
+        assert_eq!(origins, vec![1, 1, 2, 2]);
+
+        let v3 = ["intro", "# Plan", "step one", "step three"];
+        let origins = advance(&v2, &origins, &v3, 3);
+        assert_eq!(origins, vec![3, 1, 1, 2]);
+    }
+
+    #[test]
+    fn advance_handles_empty_revisions() {
+        assert_eq!(advance(&[], &[], &["a", "b"], 4), vec![4, 4]);
+        assert!(advance(&["a"], &[1], &[], 2).is_empty());
+    }
+}
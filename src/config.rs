@@ -1,5 +1,7 @@
 use std::{env, net::SocketAddr, path::PathBuf};
 
+use crate::keyhash::{parse_peppers, Pepper};
+
 #[derive(Debug, thiserror::Error)]
 pub enum ConfigError {
     #[error("missing env var {0}")]
@@ -20,9 +22,17 @@ pub struct Config {
     pub s3_secret_key: Option<String>,
     pub s3_force_path_style: bool,
     pub s3_create_bucket: bool,
-    pub api_key_pepper: Option<String>,
+    /// Newest first.
+    pub api_key_peppers: Vec<Pepper>,
+    pub share_link_secret: Option<String>,
+    pub token_signing_key: Option<String>,
     pub frontend_dist: PathBuf,
     pub front_page_path: Option<PathBuf>,
+    pub sse_max_connections: usize,
+    pub sse_max_connections_per_ip: usize,
+    pub sse_idle_timeout_secs: u64,
+    pub max_ttl_secs: Option<i64>,
+    pub admin_token: Option<String>,
 }
 
 impl Config {
@@ -48,7 +58,24 @@ impl Config {
         let s3_force_path_style = env_bool("S3_FORCE_PATH_STYLE", true);
         let s3_create_bucket = env_bool("S3_CREATE_BUCKET", true);
 
-        let api_key_pepper = env::var("API_KEY_PEPPER").ok();
+        let mut api_key_peppers = env::var("API_KEY_PEPPERS")
+            .map(|raw| parse_peppers(&raw).map_err(|e| ConfigError::Invalid("API_KEY_PEPPERS", e)))
+            .unwrap_or(Ok(Vec::new()))?;
+        // The single pepper of older deployments is the oldest one, named `default`.
+        if let Some(secret) = env::var("API_KEY_PEPPER").ok().filter(|v| !v.is_empty()) {
+            if api_key_peppers.iter().any(|p| p.id == "default") {
+                return Err(ConfigError::Invalid(
+                    "API_KEY_PEPPERS",
+                    "pepper id default is taken by API_KEY_PEPPER".to_string(),
+                ));
+            }
+            api_key_peppers.push(Pepper {
+                id: "default".to_string(),
+                secret,
+            });
+        }
+        let share_link_secret = env::var("SHARE_LINK_SECRET").ok().filter(|v| !v.is_empty());
+        let token_signing_key = env::var("TOKEN_SIGNING_KEY").ok().filter(|v| !v.is_empty());
 
         let frontend_dist = env::var("FRONTEND_DIST")
             .map(PathBuf::from)
@@ -56,6 +83,29 @@ impl Config {
 
         let front_page_path = env::var("FRONT_PAGE_PATH").map(PathBuf::from).ok();
 
+        let sse_max_connections = env::var("SSE_MAX_CONNECTIONS")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(512);
+        let sse_max_connections_per_ip = env::var("SSE_MAX_CONNECTIONS_PER_IP")
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(4);
+        let sse_idle_timeout_secs = env::var("SSE_IDLE_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(600);
+        let max_ttl_secs = env::var("MAX_TTL_SECS")
+            .ok()
+            .map(|v| {
+                v.parse::<i64>()
+                    .ok()
+                    .filter(|secs| *secs > 0)
+                    .ok_or(ConfigError::Invalid("MAX_TTL_SECS", v))
+            })
+            .transpose()?;
+        let admin_token = env::var("ADMIN_TOKEN").ok().filter(|v| !v.is_empty());
+
         Ok(Self {
             bind_addr,
             database_url,
@@ -67,9 +117,16 @@ impl Config {
             s3_secret_key,
             s3_force_path_style,
             s3_create_bucket,
-            api_key_pepper,
+            api_key_peppers,
+            share_link_secret,
+            token_signing_key,
             frontend_dist,
             front_page_path,
+            sse_max_connections,
+            sse_max_connections_per_ip,
+            sse_idle_timeout_secs,
+            max_ttl_secs,
+            admin_token,
         })
     }
 }
@@ -0,0 +1,315 @@
+use std::io::Write;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    transcript::{self, parse_messages},
+};
+
+/// Training data layouts an export can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DatasetFormat {
+    /// `{"id": ..., "conversations": [{"from": "human", "value": ...}]}`
+    ShareGpt,
+    /// `{"messages": [{"role": "user", "content": ...}]}`, as used for chat fine-tuning.
+    OpenAi,
+}
+
+impl DatasetFormat {
+    pub fn parse(raw: Option<&str>) -> Result<Self, ApiError> {
+        match raw {
+            None | Some("openai") => Ok(DatasetFormat::OpenAi),
+            Some("sharegpt") => Ok(DatasetFormat::ShareGpt),
+            Some(other) => Err(ApiError::BadRequest(format!(
+                "unsupported export format: {other}"
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DatasetFormat::ShareGpt => "sharegpt",
+            DatasetFormat::OpenAi => "openai",
+        }
+    }
+}
+
+/// The system, user and assistant text of a transcript. Messages without text (tool calls
+/// and tool results) are dropped, and consecutive messages from the same speaker are merged
+/// so turns alternate.
+pub fn turns(text: &str) -> Vec<(&'static str, String)> {
+    let mut out: Vec<(&'static str, String)> = Vec::new();
+    for message in parse_messages(text) {
+        let role = match transcript::role(&message.value) {
+            Some("system") => "system",
+            Some("user") => "user",
+            Some("assistant") => "assistant",
+            _ => continue,
+        };
+        let text = transcript::text(&message.value);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        match out.last_mut() {
+            Some((last, body)) if *last == role => {
+                body.push_str("\n\n");
+                body.push_str(text);
+            }
+            _ => out.push((role, text.to_string())),
+        }
+    }
+    out
+}
+
+/// Converts a JSONL transcript into one training record. Transcripts without both a user and
+/// an assistant turn produce nothing.
+pub fn convert(format: DatasetFormat, uuid: Uuid, text: &str) -> Option<Value> {
+    let turns = turns(text);
+    let has = |role: &str| turns.iter().any(|(r, _)| *r == role);
+    if !has("user") || !has("assistant") {
+        return None;
+    }
+
+    Some(match format {
+        DatasetFormat::OpenAi => json!({
+            "messages": turns
+                .into_iter()
+                .map(|(role, content)| json!({ "role": role, "content": content }))
+                .collect::<Vec<_>>(),
+        }),
+        DatasetFormat::ShareGpt => json!({
+            "id": uuid,
+            "conversations": turns
+                .into_iter()
+                .map(|(role, value)| {
+                    let from = match role {
+                        "user" => "human",
+                        "assistant" => "gpt",
+                        other => other,
+                    };
+                    json!({ "from": from, "value": value })
+                })
+                .collect::<Vec<_>>(),
+        }),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub format: &'static str,
+    pub exported_at: DateTime<Utc>,
+    pub requests: Vec<ManifestEntry>,
+    /// Requests that matched the filters but had no user/assistant conversation to export.
+    pub skipped: Vec<ManifestEntry>,
+    /// Set when more requests matched than one export may include.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ManifestEntry {
+    pub uuid: Uuid,
+    pub rev: i32,
+    pub sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 1-based line of the record in a plain JSONL export.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+}
+
+/// Incrementally encodes an export. Each call returns the bytes that are ready to send.
+pub enum ArchiveWriter {
+    /// One record per line, then `{"manifest": ...}` as the last line.
+    Jsonl { lines: usize },
+    /// `records/<uuid>.jsonl` per request, then `manifest.json`.
+    TarGz(Box<tar::Builder<GzEncoder<Vec<u8>>>>),
+}
+
+impl ArchiveWriter {
+    pub fn new(tar_gz: bool) -> Self {
+        if tar_gz {
+            let encoder = GzEncoder::new(Vec::new(), Compression::default());
+            ArchiveWriter::TarGz(Box::new(tar::Builder::new(encoder)))
+        } else {
+            ArchiveWriter::Jsonl { lines: 0 }
+        }
+    }
+
+    /// Path of a record inside the archive, if the archive has files.
+    pub fn file_name(&self, uuid: Uuid) -> Option<String> {
+        match self {
+            ArchiveWriter::Jsonl { .. } => None,
+            ArchiveWriter::TarGz(_) => Some(format!("records/{uuid}.jsonl")),
+        }
+    }
+
+    /// Line the next record will land on, if the export is a single JSONL stream.
+    pub fn next_line(&self) -> Option<usize> {
+        match self {
+            ArchiveWriter::Jsonl { lines } => Some(lines + 1),
+            ArchiveWriter::TarGz(_) => None,
+        }
+    }
+
+    pub fn record(&mut self, uuid: Uuid, record: &Value) -> std::io::Result<Bytes> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        match self {
+            ArchiveWriter::Jsonl { lines } => {
+                *lines += 1;
+                Ok(Bytes::from(line))
+            }
+            ArchiveWriter::TarGz(builder) => {
+                append_file(builder, &format!("records/{uuid}.jsonl"), &line)?;
+                Ok(Bytes::from(std::mem::take(builder.get_mut().get_mut())))
+            }
+        }
+    }
+
+    pub fn finish(self, manifest: &Manifest) -> std::io::Result<Bytes> {
+        match self {
+            ArchiveWriter::Jsonl { .. } => {
+                let mut line = serde_json::to_vec(&json!({ "manifest": manifest }))?;
+                line.push(b'\n');
+                Ok(Bytes::from(line))
+            }
+            ArchiveWriter::TarGz(mut builder) => {
+                let data = serde_json::to_vec_pretty(manifest)?;
+                append_file(&mut builder, "manifest.json", &data)?;
+                let mut encoder = builder.into_inner()?;
+                encoder.flush()?;
+                Ok(Bytes::from(encoder.finish()?))
+            }
+        }
+    }
+}
+
+fn append_file<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> std::io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(Utc::now().timestamp().max(0) as u64);
+    builder.append_data(&mut header, path, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    const TRANSCRIPT: &str = concat!(
+        "{\"type\":\"user\",\"message\":{\"role\":\"user\",\"content\":\"List the files\"}}\n",
+        "{\"type\":\"assistant\",\"message\":{\"role\":\"assistant\",\"content\":[{\"type\":\"text\",\"text\":\"Running ls.\"},{\"type\":\"tool_use\",\"name\":\"bash\",\"input\":{}}]}}\n",
+        "{\"type\":\"user\",\"message\":{\"role\":\"user\",\"content\":[{\"type\":\"tool_result\",\"content\":\"a.rs\"}]}}\n",
+        "{\"type\":\"assistant\",\"message\":{\"role\":\"assistant\",\"content\":\"There is one file.\"}}\n",
+    );
+
+    #[test]
+    fn converts_to_both_formats() {
+        let uuid = Uuid::from_u128(1);
+        let openai = convert(DatasetFormat::OpenAi, uuid, TRANSCRIPT).unwrap();
+        assert_eq!(
+            openai,
+            json!({"messages": [
+                {"role": "user", "content": "List the files"},
+                {"role": "assistant", "content": "Running ls.\n\nThere is one file."},
+            ]})
+        );
+
+        let sharegpt = convert(DatasetFormat::ShareGpt, uuid, TRANSCRIPT).unwrap();
+        assert_eq!(sharegpt["id"], uuid.to_string());
+        assert_eq!(sharegpt["conversations"][0]["from"], "human");
+        assert_eq!(sharegpt["conversations"][1]["from"], "gpt");
+
+        assert!(convert(
+            DatasetFormat::OpenAi,
+            uuid,
+            "{\"role\":\"user\",\"content\":\"hi\"}\n"
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn tar_gz_contains_records_and_manifest() {
+        let uuid = Uuid::from_u128(2);
+        let mut writer = ArchiveWriter::new(true);
+        let mut out = writer
+            .record(uuid, &json!({"messages": []}))
+            .unwrap()
+            .to_vec();
+        let manifest = Manifest {
+            format: "openai",
+            exported_at: Utc::now(),
+            requests: vec![ManifestEntry {
+                uuid,
+                rev: 3,
+                sha256: "abc".to_string(),
+                file: writer.file_name(uuid),
+                line: writer.next_line(),
+            }],
+            skipped: Vec::new(),
+            truncated: false,
+        };
+        out.extend_from_slice(&writer.finish(&manifest).unwrap());
+
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(out.as_slice()));
+        let mut names = Vec::new();
+        for entry in archive.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut body = String::new();
+            entry.read_to_string(&mut body).unwrap();
+            names.push((entry.path().unwrap().display().to_string(), body));
+        }
+        assert_eq!(names[0].0, format!("records/{uuid}.jsonl"));
+        assert_eq!(names[0].1, "{\"messages\":[]}\n");
+        assert_eq!(names[1].0, "manifest.json");
+        assert!(names[1].1.contains("\"rev\": 3"));
+    }
+
+    #[test]
+    fn jsonl_ends_with_the_manifest() {
+        let uuid = Uuid::from_u128(3);
+        let mut writer = ArchiveWriter::new(false);
+        let entry = ManifestEntry {
+            uuid,
+            rev: 1,
+            sha256: "abc".to_string(),
+            file: writer.file_name(uuid),
+            line: writer.next_line(),
+        };
+        let mut out = writer
+            .record(uuid, &json!({"messages": []}))
+            .unwrap()
+            .to_vec();
+        let manifest = Manifest {
+            format: "openai",
+            exported_at: Utc::now(),
+            requests: vec![entry],
+            skipped: Vec::new(),
+            truncated: false,
+        };
+        out.extend_from_slice(&writer.finish(&manifest).unwrap());
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "{\"messages\":[]}");
+        let last: Value = serde_json::from_str(lines[1]).unwrap();
+        let listed = &last["manifest"]["requests"][0];
+        assert_eq!(listed["uuid"], uuid.to_string());
+        assert_eq!(listed["line"], 1);
+        assert!(listed.get("file").is_none());
+        assert_eq!(last["manifest"]["truncated"], false);
+    }
+}
@@ -0,0 +1,216 @@
+use axum::{
+    body::Body,
+    http::{header::CONTENT_TYPE, HeaderValue},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::{capture_diff_slices, Algorithm, DiffOp, TextDiff};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    models::DiffResponse,
+    revisions::RevisionContent,
+    transcript::{parse_messages, Message, MessageEntry},
+    util::ContentKind,
+};
+
+const CONTEXT_LINES: usize = 3;
+
+#[derive(Deserialize)]
+pub struct DiffQuery {
+    pub from: i32,
+    pub to: i32,
+    pub format: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffFormat {
+    Json,
+    Unified,
+}
+
+impl DiffQuery {
+    pub fn validate(&self) -> Result<DiffFormat, ApiError> {
+        if self.from < 1 || self.to < 1 {
+            return Err(ApiError::BadRequest("from and to must be >= 1".to_string()));
+        }
+        match self.format.as_deref() {
+            None | Some("json") => Ok(DiffFormat::Json),
+            Some("diff") | Some("unified") => Ok(DiffFormat::Unified),
+            Some(other) => Err(ApiError::BadRequest(format!(
+                "unsupported diff format: {other}"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct MessageDiff {
+    pub added: Vec<MessageEntry>,
+    pub removed: Vec<MessageEntry>,
+    pub changed: Vec<MessageChange>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MessageChange {
+    pub from_index: usize,
+    pub to_index: usize,
+    pub before: Value,
+    pub after: Value,
+}
+
+/// Unified line diff between two texts, labelled with the given file names.
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+/// Per-message diff of two JSONL transcripts. Message indexes are 1-based positions among
+/// the non-empty lines; a replaced run is reported as `changed` pairs, with any excess
+/// on either side reported as `removed` or `added`.
+pub fn messages(old: &str, new: &str) -> MessageDiff {
+    let old_msgs = parse_messages(old);
+    let new_msgs = parse_messages(new);
+    let old_keys: Vec<String> = old_msgs.iter().map(|m| m.value.to_string()).collect();
+    let new_keys: Vec<String> = new_msgs.iter().map(|m| m.value.to_string()).collect();
+
+    let entry = |msgs: &[Message], idx: usize| MessageEntry {
+        index: idx + 1,
+        line: msgs[idx].line,
+        message: msgs[idx].value.clone(),
+    };
+
+    let mut out = MessageDiff::default();
+    for op in capture_diff_slices(Algorithm::Myers, &old_keys, &new_keys) {
+        match op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete {
+                old_index, old_len, ..
+            } => {
+                for idx in old_index..old_index + old_len {
+                    out.removed.push(entry(&old_msgs, idx));
+                }
+            }
+            DiffOp::Insert {
+                new_index, new_len, ..
+            } => {
+                for idx in new_index..new_index + new_len {
+                    out.added.push(entry(&new_msgs, idx));
+                }
+            }
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                let paired = old_len.min(new_len);
+                for offset in 0..paired {
+                    out.changed.push(MessageChange {
+                        from_index: old_index + offset + 1,
+                        to_index: new_index + offset + 1,
+                        before: old_msgs[old_index + offset].value.clone(),
+                        after: new_msgs[new_index + offset].value.clone(),
+                    });
+                }
+                for idx in old_index + paired..old_index + old_len {
+                    out.removed.push(entry(&old_msgs, idx));
+                }
+                for idx in new_index + paired..new_index + new_len {
+                    out.added.push(entry(&new_msgs, idx));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Renders the diff between two revisions of `uuid` in the requested format.
+pub fn render(
+    uuid: Uuid,
+    from: &RevisionContent,
+    to: &RevisionContent,
+    format: DiffFormat,
+) -> Response {
+    let old = from.text();
+    let new = to.text();
+
+    if format == DiffFormat::Unified {
+        let text = unified(
+            &old,
+            &new,
+            &format!("{uuid}/rev-{}", from.rev),
+            &format!("{uuid}/rev-{}", to.rev),
+        );
+        let mut resp = Response::new(Body::from(text));
+        resp.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/x-diff; charset=utf-8"),
+        );
+        return resp;
+    }
+
+    let jsonl = ContentKind::Jsonl.canonical_type();
+    let (unified_text, message_diff) = if from.content_type == jsonl && to.content_type == jsonl {
+        (None, Some(messages(&old, &new)))
+    } else {
+        (
+            Some(unified(
+                &old,
+                &new,
+                &format!("rev-{}", from.rev),
+                &format!("rev-{}", to.rev),
+            )),
+            None,
+        )
+    };
+
+    Json(DiffResponse {
+        uuid,
+        from: from.rev,
+        to: to.rev,
+        from_content_type: from.content_type.clone(),
+        to_content_type: to.content_type.clone(),
+        unified: unified_text,
+        messages: message_diff,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_marks_changed_lines() {
+        let text = unified("# Title\nold\n", "# Title\nnew\n", "a", "b");
+        assert!(text.contains("-old"));
+        assert!(text.contains("+new"));
+        assert!(text.starts_with("--- a\n+++ b\n"));
+    }
+
+    #[test]
+    fn messages_reports_added_removed_and_changed() {
+        let old = "{\"role\":\"user\",\"content\":\"hi\"}\n{\"role\":\"assistant\",\"content\":\"hello\"}\n{\"role\":\"user\",\"content\":\"bye\"}\n";
+        let new = "{\"role\":\"user\",\"content\":\"hi\"}\n{\"role\":\"assistant\",\"content\":\"hey\"}\n{\"role\":\"user\",\"content\":\"more\"}\n{\"role\":\"assistant\",\"content\":\"ok\"}\n";
+        let diff = messages(old, new);
+        assert_eq!(diff.changed.len(), 2);
+        assert_eq!(diff.changed[0].from_index, 2);
+        assert_eq!(diff.changed[0].after["content"], "hey");
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].index, 4);
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn messages_ignores_formatting_only_changes() {
+        let diff = messages("{\"a\": 1}\n", "{\"a\":1}\n\n");
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+    }
+}
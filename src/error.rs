@@ -1,5 +1,8 @@
 use axum::{
-    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
+    http::{
+        header::{RETRY_AFTER, WWW_AUTHENTICATE},
+        HeaderValue, StatusCode,
+    },
     response::{IntoResponse, Response},
     Json,
 };
@@ -11,10 +14,23 @@ pub enum ApiError {
     BadRequest(String),
     #[error("unauthorized")]
     Unauthorized,
+    /// Authenticated, but the credential does not allow this call.
+    #[error("forbidden: {0}")]
+    Forbidden(String),
+    /// A public read of a passphrase-protected request without a valid passphrase.
+    #[error("passphrase required: {0}")]
+    PassphraseRequired(String),
     #[error("not found")]
     NotFound,
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("gone: {0}")]
+    Gone(String),
     #[error("payload too large")]
     PayloadTooLarge,
+    /// An upload that would take the account past its tier's storage or request limit.
+    #[error("quota exceeded: {0}")]
+    QuotaExceeded(String),
     #[error("rate limited")]
     RateLimited { retry_after_secs: u64 },
     #[error("storage error: {0}")]
@@ -34,16 +50,41 @@ struct ErrorBody {
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         match self {
-            ApiError::BadRequest(msg) => json_error(
-                StatusCode::BAD_REQUEST,
-                "bad_request",
-                Some(msg),
+            ApiError::BadRequest(msg) => {
+                json_error(StatusCode::BAD_REQUEST, "bad_request", Some(msg), None)
+            }
+            ApiError::Unauthorized => {
+                json_error(StatusCode::UNAUTHORIZED, "unauthorized", None, None)
+            }
+            ApiError::Forbidden(msg) => {
+                json_error(StatusCode::FORBIDDEN, "forbidden", Some(msg), None)
+            }
+            ApiError::PassphraseRequired(msg) => {
+                let mut resp = json_error(
+                    StatusCode::UNAUTHORIZED,
+                    "passphrase_required",
+                    Some(msg),
+                    None,
+                );
+                resp.headers_mut().insert(
+                    WWW_AUTHENTICATE,
+                    HeaderValue::from_static("Passphrase realm=\"prompt-request\""),
+                );
+                resp
+            }
+            ApiError::NotFound => json_error(StatusCode::NOT_FOUND, "not_found", None, None),
+            ApiError::Conflict(msg) => {
+                json_error(StatusCode::CONFLICT, "conflict", Some(msg), None)
+            }
+            ApiError::Gone(msg) => json_error(StatusCode::GONE, "gone", Some(msg), None),
+            ApiError::PayloadTooLarge => json_error(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "payload_too_large",
+                None,
                 None,
             ),
-            ApiError::Unauthorized => json_error(StatusCode::UNAUTHORIZED, "unauthorized", None, None),
-            ApiError::NotFound => json_error(StatusCode::NOT_FOUND, "not_found", None, None),
-            ApiError::PayloadTooLarge => {
-                json_error(StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", None, None)
+            ApiError::QuotaExceeded(msg) => {
+                json_error(StatusCode::FORBIDDEN, "quota_exceeded", Some(msg), None)
             }
             ApiError::RateLimited { retry_after_secs } => json_error(
                 StatusCode::TOO_MANY_REQUESTS,
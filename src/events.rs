@@ -0,0 +1,196 @@
+use std::{
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgListener, PgPool, Postgres, Transaction};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+/// Postgres channel used to fan revision events out to every instance.
+pub const CHANNEL: &str = "request_events";
+
+const BROADCAST_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RevisionEvent {
+    pub uuid: Uuid,
+    pub rev: i32,
+    pub previous_rev: i32,
+    pub content_type: String,
+    pub size_bytes: i32,
+    pub sha256: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// What an SSE subscriber receives: the revision metadata and, when requested, the JSONL
+/// messages added on top of the previous revision.
+#[derive(Serialize)]
+pub struct EventMessage {
+    #[serde(flatten)]
+    pub event: RevisionEvent,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<Vec<serde_json::Value>>,
+}
+
+/// Queues a notification that is delivered to listeners when `tx` commits.
+pub async fn notify(
+    tx: &mut Transaction<'_, Postgres>,
+    event: &RevisionEvent,
+) -> Result<(), ApiError> {
+    let payload = serde_json::to_string(event).map_err(|e| ApiError::Internal(e.to_string()))?;
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(CHANNEL)
+        .bind(payload)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+/// Local fan-out of revision events to SSE subscribers, with bounded connection counts.
+pub struct EventHub {
+    sender: broadcast::Sender<RevisionEvent>,
+    max_connections: usize,
+    max_connections_per_ip: usize,
+    pub idle_timeout: Duration,
+    total: AtomicUsize,
+    per_ip: DashMap<IpAddr, usize>,
+}
+
+impl EventHub {
+    pub fn new(
+        max_connections: usize,
+        max_connections_per_ip: usize,
+        idle_timeout: Duration,
+    ) -> Self {
+        let (sender, _) = broadcast::channel(BROADCAST_CAPACITY);
+        Self {
+            sender,
+            max_connections,
+            max_connections_per_ip,
+            idle_timeout,
+            total: AtomicUsize::new(0),
+            per_ip: DashMap::new(),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<RevisionEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Reserves a connection slot for `ip`; the slot is released when the guard drops.
+    pub fn connect(self: &Arc<Self>, ip: IpAddr) -> Result<ConnectionGuard, ApiError> {
+        {
+            let mut count = self.per_ip.entry(ip).or_insert(0);
+            if *count >= self.max_connections_per_ip {
+                return Err(ApiError::RateLimited {
+                    retry_after_secs: 5,
+                });
+            }
+            *count += 1;
+        }
+
+        if self.total.fetch_add(1, Ordering::SeqCst) >= self.max_connections {
+            self.release(ip);
+            return Err(ApiError::RateLimited {
+                retry_after_secs: 5,
+            });
+        }
+
+        Ok(ConnectionGuard {
+            hub: self.clone(),
+            ip,
+        })
+    }
+
+    fn release(&self, ip: IpAddr) {
+        self.total.fetch_sub(1, Ordering::SeqCst);
+        if let Some(mut count) = self.per_ip.get_mut(&ip) {
+            *count = count.saturating_sub(1);
+        }
+        self.per_ip.remove_if(&ip, |_, count| *count == 0);
+    }
+}
+
+pub struct ConnectionGuard {
+    hub: Arc<EventHub>,
+    ip: IpAddr,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.hub.release(self.ip);
+    }
+}
+
+/// Forwards `NOTIFY` payloads from Postgres into the hub. `PgListener` reconnects on its own;
+/// we only back off when a receive fails outright.
+pub fn spawn_listener(pool: PgPool, hub: Arc<EventHub>) {
+    tokio::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect_with(&pool).await {
+                Ok(listener) => listener,
+                Err(err) => {
+                    tracing::warn!(error = %err, "event listener connect failed");
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+            if let Err(err) = listener.listen(CHANNEL).await {
+                tracing::warn!(error = %err, "event listener LISTEN failed");
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                continue;
+            }
+
+            loop {
+                match listener.recv().await {
+                    Ok(notification) => {
+                        match serde_json::from_str::<RevisionEvent>(notification.payload()) {
+                            Ok(event) => {
+                                let _ = hub.sender.send(event);
+                            }
+                            Err(err) => {
+                                tracing::warn!(error = %err, "invalid event payload");
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        tracing::warn!(error = %err, "event listener receive failed");
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connection_limits_are_enforced_and_released() {
+        let hub = Arc::new(EventHub::new(2, 1, Duration::from_secs(1)));
+        let a: IpAddr = "10.0.0.1".parse().unwrap();
+        let b: IpAddr = "10.0.0.2".parse().unwrap();
+        let c: IpAddr = "10.0.0.3".parse().unwrap();
+
+        let guard_a = hub.connect(a).unwrap();
+        assert!(hub.connect(a).is_err());
+        let _guard_b = hub.connect(b).unwrap();
+        assert!(hub.connect(c).is_err());
+
+        drop(guard_a);
+        assert!(hub.connect(c).is_ok());
+    }
+}
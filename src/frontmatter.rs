@@ -0,0 +1,105 @@
+use serde_json::{Map, Value};
+
+use crate::error::ApiError;
+
+/// YAML front matter parsed from the start of a Markdown body.
+#[derive(Debug)]
+pub struct FrontMatter {
+    pub fields: Map<String, Value>,
+    /// Bytes taken by the block, including both delimiter lines.
+    pub len: usize,
+}
+
+/// Finds a leading `---` block closed by `---` or `...`, returning the YAML between the
+/// delimiters and the length of the whole block. Bodies that do not start with `---` have
+/// no front matter; an opening delimiter without a closing one is an error.
+pub fn split(body: &str) -> Result<Option<(&str, usize)>, ApiError> {
+    let Some(rest) = body
+        .strip_prefix("---\n")
+        .or_else(|| body.strip_prefix("---\r\n"))
+    else {
+        return Ok(None);
+    };
+    let opening = body.len() - rest.len();
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        let trimmed = line.trim_end();
+        if trimmed == "---" || trimmed == "..." {
+            return Ok(Some((&rest[..offset], opening + offset + line.len())));
+        }
+        offset += line.len();
+    }
+    Err(ApiError::BadRequest(
+        "front matter is missing its closing --- line".to_string(),
+    ))
+}
+
+/// Parses the front matter of a Markdown body into a JSON object.
+pub fn parse(body: &str) -> Result<Option<FrontMatter>, ApiError> {
+    let Some((yaml, len)) = split(body)? else {
+        return Ok(None);
+    };
+
+    let value: serde_yaml::Value = serde_yaml::from_str(yaml)
+        .map_err(|e| ApiError::BadRequest(format!("invalid front matter: {e}")))?;
+    let fields = match value {
+        serde_yaml::Value::Null => Map::new(),
+        serde_yaml::Value::Mapping(_) => match serde_json::to_value(value) {
+            Ok(Value::Object(map)) => map,
+            _ => {
+                return Err(ApiError::BadRequest(
+                    "invalid front matter: keys must be strings".to_string(),
+                ))
+            }
+        },
+        _ => {
+            return Err(ApiError::BadRequest(
+                "invalid front matter: expected key: value pairs".to_string(),
+            ))
+        }
+    };
+
+    Ok(Some(FrontMatter { fields, len }))
+}
+
+/// The body with any front matter removed. Malformed front matter is left in place.
+pub fn strip(body: &str) -> &str {
+    match split(body) {
+        Ok(Some((_, len))) => &body[len..],
+        _ => body,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fields_and_length() {
+        let body = "---\ntitle: Fix CI\nmodel: opus\ntags: [bugfix, ci]\n---\n# Body\n";
+        let fm = parse(body).unwrap().unwrap();
+        assert_eq!(fm.fields["title"], "Fix CI");
+        assert_eq!(fm.fields["tags"], serde_json::json!(["bugfix", "ci"]));
+        assert_eq!(&body[fm.len..], "# Body\n");
+        assert_eq!(strip(body), "# Body\n");
+
+        assert!(parse("# No front matter\n---\n").unwrap().is_none());
+        assert!(parse("---\n---\nbody").unwrap().unwrap().fields.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_front_matter() {
+        for body in [
+            "---\ntitle: x\n# never closed\n",
+            "---\ntitle: [unclosed\n---\n",
+            "---\n- a\n- b\n---\n",
+            "---\n? [a, b]\n: one\n---\n",
+        ] {
+            assert!(
+                matches!(parse(body), Err(ApiError::BadRequest(_))),
+                "{body:?}"
+            );
+        }
+    }
+}
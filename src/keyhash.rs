@@ -0,0 +1,171 @@
+use hmac::Mac;
+
+use crate::util::{hmac_sha256, sha256_hex};
+
+/// Version tag of the current hash format, `v1:<pepper id>:<hex HMAC-SHA256>`.
+const HASH_VERSION: &str = "v1";
+/// Pepper id used when none is configured. Its hashes are keyed with an empty secret.
+const UNPEPPERED_ID: &str = "none";
+const MAX_PEPPER_ID_CHARS: usize = 16;
+
+/// A secret mixed into API key hashes, named so stored hashes can say which one they used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pepper {
+    pub id: String,
+    pub secret: String,
+}
+
+/// Parses `API_KEY_PEPPERS`: comma-separated `id=secret` pairs, newest first.
+pub fn parse_peppers(raw: &str) -> Result<Vec<Pepper>, String> {
+    let mut peppers: Vec<Pepper> = Vec::new();
+    for pair in raw.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (id, secret) = pair
+            .split_once('=')
+            .ok_or_else(|| format!("expected id=secret, got {pair:?}"))?;
+        let valid_id = !id.is_empty()
+            && id.chars().count() <= MAX_PEPPER_ID_CHARS
+            && id != UNPEPPERED_ID
+            && id
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+        if !valid_id {
+            return Err(format!(
+                "pepper ids are 1-{MAX_PEPPER_ID_CHARS} characters of a-z, 0-9, - and _, \
+                 and not {UNPEPPERED_ID:?}"
+            ));
+        }
+        if secret.is_empty() {
+            return Err(format!("pepper {id} has an empty secret"));
+        }
+        if peppers.iter().any(|p| p.id == id) {
+            return Err(format!("pepper {id} is listed twice"));
+        }
+        peppers.push(Pepper {
+            id: id.to_string(),
+            secret: secret.to_string(),
+        });
+    }
+    Ok(peppers)
+}
+
+/// Hashes API keys with the newest pepper and recognizes hashes made with any configured
+/// one, so peppers can be rotated without locking accounts out.
+#[derive(Clone, Debug)]
+pub struct KeyHasher {
+    /// Newest first; never empty.
+    peppers: Vec<Pepper>,
+}
+
+impl KeyHasher {
+    pub fn new(peppers: Vec<Pepper>) -> Self {
+        let peppers = if peppers.is_empty() {
+            vec![unpeppered()]
+        } else {
+            peppers
+        };
+        Self { peppers }
+    }
+
+    /// The hash to store for `key`, made with the newest pepper.
+    pub fn hash(&self, key: &str) -> String {
+        hmac_hash(&self.peppers[0], key)
+    }
+
+    /// Every hash `key` may be stored under: one per configured pepper, the unkeyed one
+    /// written while no pepper was configured, and the unversioned SHA-256 hashes written
+    /// before hashes were versioned. A key found under any of them should be rehashed with
+    /// [`KeyHasher::hash`].
+    pub fn candidates(&self, key: &str) -> Vec<String> {
+        let mut hashes: Vec<String> = self.peppers.iter().map(|p| hmac_hash(p, key)).collect();
+        if !self.peppers.iter().any(|p| p.id == UNPEPPERED_ID) {
+            hashes.push(hmac_hash(&unpeppered(), key));
+        }
+        for pepper in &self.peppers {
+            hashes.push(legacy_hash(&pepper.secret, key));
+        }
+        if !self.peppers.iter().any(|p| p.secret.is_empty()) {
+            hashes.push(legacy_hash("", key));
+        }
+        hashes
+    }
+}
+
+fn unpeppered() -> Pepper {
+    Pepper {
+        id: UNPEPPERED_ID.to_string(),
+        secret: String::new(),
+    }
+}
+
+fn hmac_hash(pepper: &Pepper, key: &str) -> String {
+    let mac = hmac_sha256(pepper.secret.as_bytes(), &[key.as_bytes()]);
+    format!(
+        "{HASH_VERSION}:{}:{}",
+        pepper.id,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+/// SHA-256 over `pepper || key`, as stored before hashes were versioned.
+fn legacy_hash(pepper: &str, key: &str) -> String {
+    sha256_hex(format!("{pepper}{key}").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pepper(id: &str, secret: &str) -> Pepper {
+        Pepper {
+            id: id.to_string(),
+            secret: secret.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_pepper_lists() {
+        assert_eq!(
+            parse_peppers("b=new, a=old").unwrap(),
+            vec![pepper("b", "new"), pepper("a", "old")]
+        );
+        assert!(parse_peppers("").unwrap().is_empty());
+        assert!(parse_peppers("nosecret").is_err());
+        assert!(parse_peppers("a=").is_err());
+        assert!(parse_peppers("a=x,a=y").is_err());
+        assert!(parse_peppers("a:b=x").is_err());
+        assert!(parse_peppers("none=x").is_err());
+    }
+
+    #[test]
+    fn hashes_with_the_newest_pepper() {
+        let hasher = KeyHasher::new(vec![pepper("b", "new"), pepper("a", "old")]);
+        let hash = hasher.hash("prq_key");
+        assert!(hash.starts_with("v1:b:"));
+        assert_ne!(
+            hash,
+            KeyHasher::new(vec![pepper("b", "other")]).hash("prq_key")
+        );
+
+        let old = KeyHasher::new(vec![pepper("a", "old")]).hash("prq_key");
+        let candidates = hasher.candidates("prq_key");
+        assert_eq!(candidates[0], hash);
+        assert!(candidates.contains(&old));
+        assert!(candidates.contains(&legacy_hash("old", "prq_key")));
+        assert!(candidates.contains(&legacy_hash("", "prq_key")));
+    }
+
+    #[test]
+    fn works_without_peppers() {
+        let hasher = KeyHasher::new(Vec::new());
+        assert!(hasher.hash("prq_key").starts_with("v1:none:"));
+        assert_eq!(hasher.candidates("prq_key").len(), 2);
+    }
+
+    #[test]
+    fn keys_minted_without_peppers_survive_adding_one() {
+        let before = KeyHasher::new(Vec::new()).hash("prq_key");
+        let after = KeyHasher::new(vec![pepper("a", "secret")]);
+        assert_ne!(after.hash("prq_key"), before);
+        assert!(after.candidates("prq_key").contains(&before));
+    }
+}
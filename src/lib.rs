@@ -1,28 +1,41 @@
+pub mod access;
 pub mod auth;
+pub mod blame;
 pub mod config;
+pub mod dataset;
+pub mod diff;
 pub mod error;
+pub mod events;
+pub mod frontmatter;
+pub mod keyhash;
 pub mod models;
+pub mod passphrase;
+pub mod quota;
 pub mod ratelimit;
+pub mod retention;
+pub mod revisions;
 pub mod routes;
+pub mod scopes;
+pub mod simhash;
 pub mod storage;
+pub mod summary;
+pub mod tokens;
+pub mod transcript;
+pub mod upload;
 pub mod util;
 
-use std::{
-    net::SocketAddr,
-    path::PathBuf,
-    sync::Arc,
-    time::Duration,
-};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc, time::Duration};
 
 use axum::{
     body::Body,
     extract::DefaultBodyLimit,
     http::{Request, StatusCode, Uri},
     response::IntoResponse,
-    routing::{get, post, put},
+    routing::{delete, get, post, put},
     Router,
 };
 use sqlx::{postgres::PgPoolOptions, PgPool};
+use tokio::sync::Semaphore;
 use tower::util::{service_fn, ServiceExt};
 use tower_http::{
     services::{ServeDir, ServeFile},
@@ -31,8 +44,12 @@ use tower_http::{
 
 use crate::{
     config::Config,
+    events::EventHub,
+    keyhash::KeyHasher,
     ratelimit::RateLimiter,
-    routes::{accounts, public, requests},
+    routes::{
+        accounts, admin, commits, export, keys, public, relations, requests, sharing, similar, tags,
+    },
     storage::s3::S3Store,
 };
 
@@ -43,15 +60,31 @@ pub struct AppState {
     pub account_limiter: Arc<RateLimiter>,
     pub public_read_limiter: Arc<RateLimiter>,
     pub account_create_limiter: Arc<RateLimiter>,
+    /// Throttles passphrase attempts per client IP.
+    pub passphrase_limiter: Arc<RateLimiter>,
+    /// Caps concurrent passphrase verifications.
+    pub passphrase_permits: Arc<Semaphore>,
     pub front_page: Arc<String>,
-    pub api_key_pepper: Option<String>,
+    /// Hashes API keys for storage and lookup.
+    pub key_hasher: Arc<KeyHasher>,
+    /// Key for signing share links.
+    pub share_link_secret: Arc<String>,
+    /// Key for signing upload tokens.
+    pub token_signing_key: Arc<String>,
     pub frontend_dist: PathBuf,
+    pub events: Arc<EventHub>,
+    /// Upper bound on any request's or revision's time-to-live.
+    pub max_ttl_secs: Option<i64>,
+    /// Bearer token for the admin API, which is disabled when unset.
+    pub admin_token: Option<String>,
 }
 
 pub async fn run() -> Result<(), Box<dyn std::error::Error>> {
     init_tracing();
     let cfg = Config::from_env()?;
     let state = build_state(&cfg).await?;
+    retention::spawn_sweeper(state.clone());
+    routes::requests::spawn_search_backfill(state.clone());
     let app = build_router(state);
 
     let listener = tokio::net::TcpListener::bind(cfg.bind_addr).await?;
@@ -77,38 +110,108 @@ pub async fn build_state(cfg: &Config) -> Result<AppState, Box<dyn std::error::E
 
     let front_page = load_front_page(cfg)?;
 
+    let share_link_secret = cfg.share_link_secret.clone().unwrap_or_else(|| {
+        tracing::warn!("SHARE_LINK_SECRET is not set; share links will not survive a restart");
+        util::generate_share_token()
+    });
+    if cfg.api_key_peppers.is_empty() {
+        tracing::warn!("API_KEY_PEPPERS is not set; API key hashes are not keyed with a secret");
+    }
+    let token_signing_key = cfg.token_signing_key.clone().unwrap_or_else(|| {
+        tracing::warn!("TOKEN_SIGNING_KEY is not set; upload tokens will not survive a restart");
+        util::generate_share_token()
+    });
+
+    let events = Arc::new(EventHub::new(
+        cfg.sse_max_connections,
+        cfg.sse_max_connections_per_ip,
+        Duration::from_secs(cfg.sse_idle_timeout_secs),
+    ));
+    events::spawn_listener(pool.clone(), events.clone());
+
     Ok(AppState {
         pool,
         store: Arc::new(store),
         account_limiter: Arc::new(RateLimiter::new(Duration::from_secs(1))),
         public_read_limiter: Arc::new(RateLimiter::new(Duration::from_secs(1))),
         account_create_limiter: Arc::new(RateLimiter::new(Duration::from_secs(3600))),
+        passphrase_limiter: Arc::new(RateLimiter::new(Duration::from_secs(5))),
+        passphrase_permits: Arc::new(Semaphore::new(passphrase::MAX_CONCURRENT_VERIFICATIONS)),
         front_page: Arc::new(front_page),
-        api_key_pepper: cfg.api_key_pepper.clone(),
+        key_hasher: Arc::new(KeyHasher::new(cfg.api_key_peppers.clone())),
+        share_link_secret: Arc::new(share_link_secret),
+        token_signing_key: Arc::new(token_signing_key),
         frontend_dist: cfg.frontend_dist.clone(),
+        events,
+        max_ttl_secs: cfg.max_ttl_secs,
+        admin_token: cfg.admin_token.clone(),
     })
 }
 
 pub fn build_router(state: AppState) -> Router {
     let api = Router::new()
         .route("/accounts", post(accounts::create_account))
+        .route(
+            "/account",
+            get(accounts::get_account).patch(accounts::patch_account),
+        )
+        .route("/account/usage", get(accounts::get_usage))
+        .route("/keys", post(keys::create_key).get(keys::list_keys))
+        .route("/keys/:id", delete(keys::revoke_key))
+        .route("/tokens", post(routes::tokens::create_token))
+        .route("/admin/tiers", get(admin::list_tiers))
+        .route("/admin/tiers/:name", put(admin::put_tier))
+        .route("/admin/accounts/:id/tier", put(admin::set_account_tier))
         .route(
             "/requests",
             post(requests::create_request).get(requests::list_requests),
         )
+        .route("/requests/search", get(requests::search_requests))
         .route(
             "/requests/:uuid",
-            put(requests::update_request).delete(requests::delete_request),
-        )
-        .route(
-            "/requests/:uuid/revisions",
-            get(requests::list_revisions),
+            put(requests::update_request)
+                .patch(requests::patch_request)
+                .delete(requests::delete_request),
         )
+        .route("/requests/:uuid/revisions", get(requests::list_revisions))
         .route(
             "/requests/:uuid/revisions/:rev",
             get(requests::get_revision_metadata),
         )
-        .layer(DefaultBodyLimit::max(util::MAX_UPLOAD_BYTES));
+        .route("/requests/:uuid/append", post(requests::append_request))
+        .route("/requests/:uuid/diff", get(requests::diff_revisions))
+        .route("/requests/:uuid/blame", get(requests::blame_revision))
+        .route("/requests/:uuid/excerpts", post(requests::create_excerpt))
+        .route(
+            "/requests/:uuid/tags",
+            get(tags::list_request_tags).post(tags::add_request_tags),
+        )
+        .route(
+            "/requests/:uuid/tags/:tag",
+            delete(tags::remove_request_tag),
+        )
+        .route("/tags", get(tags::list_tags))
+        .route("/commits/:sha", get(commits::lookup))
+        .route("/export", get(export::export))
+        .route("/requests/:uuid/related", get(relations::get_related))
+        .route("/requests/:uuid/similar", get(similar::get_similar))
+        .route(
+            "/requests/:uuid/share-token",
+            post(sharing::rotate_share_token),
+        )
+        .route(
+            "/requests/:uuid/share-links",
+            get(sharing::list_share_links).post(sharing::create_share_link),
+        )
+        .route(
+            "/requests/:uuid/share-links/:id",
+            delete(sharing::revoke_share_link),
+        )
+        .route(
+            "/requests/:uuid/parent",
+            put(relations::put_parent).delete(relations::delete_parent),
+        )
+        .layer(DefaultBodyLimit::max(util::MAX_BODY_BYTES));
 
     let frontend = frontend_router(state.frontend_dist.clone());
 
@@ -116,6 +219,10 @@ pub fn build_router(state: AppState) -> Router {
         .merge(frontend)
         .route("/", get(public::front_page))
         .route("/:uuid", get(public::get_raw))
+        .route("/:uuid/diff", get(public::get_diff))
+        .route("/:uuid/events", get(public::get_events))
+        .route("/:uuid/meta", get(public::get_meta))
+        .route("/:uuid/unlock", post(public::unlock))
         .route("/healthz", get(|| async { "ok" }))
         .nest("/api", api)
         .with_state(state)
@@ -1,13 +1,83 @@
 use chrono::{DateTime, Utc};
 use serde::Serialize;
+use serde_json::Value;
+use sqlx::types::Json;
 use uuid::Uuid;
 
+use crate::{diff::MessageDiff, transcript::MessageEntry};
+
 #[derive(Serialize)]
 pub struct CreateAccountResponse {
     pub api_key: String,
     pub comment_for_model: String,
 }
 
+#[derive(Serialize)]
+pub struct ApiKeyCreatedResponse {
+    pub id: Uuid,
+    pub name: String,
+    /// Shown once; only its hash is stored.
+    pub api_key: String,
+    pub scopes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_uuids: Option<Vec<Uuid>>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct UploadTokenResponse {
+    pub token: String,
+    pub scopes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_uuid: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ApiKeyInfo {
+    pub id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_uuids: Option<Vec<Uuid>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Whether this is the key the listing was requested with.
+    pub current: bool,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct AccountSettings {
+    pub created_at: DateTime<Utc>,
+    /// Name of the account's tier; see `GET /api/account/usage` for its limits.
+    pub tier: String,
+    /// Time-to-live of new requests that do not set one.
+    pub default_ttl_secs: Option<i64>,
+}
+
+/// A named set of limits; every account is on exactly one tier.
+#[derive(Clone, Debug, Serialize, sqlx::FromRow)]
+pub struct Tier {
+    pub name: String,
+    pub max_total_bytes: i64,
+    pub max_requests: i64,
+    pub max_upload_bytes: i64,
+    /// Minimum time between authenticated API calls.
+    pub min_request_interval_ms: i64,
+}
+
+#[derive(Serialize)]
+pub struct AccountUsage {
+    pub tier: Tier,
+    /// Bytes of stored objects; appended revisions only count the bytes they add.
+    pub total_bytes: i64,
+    pub request_count: i64,
+}
+
 #[derive(Serialize)]
 pub struct RequestCreatedResponse {
     pub uuid: Uuid,
@@ -16,6 +86,132 @@ pub struct RequestCreatedResponse {
     pub size_bytes: i32,
     pub sha256: String,
     pub created_at: DateTime<Utc>,
+    /// Set on create when the account already has a near-duplicate of this upload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub similar_to: Option<SimilarHint>,
+    /// Set when the upload created the request or changed its visibility.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_token: Option<String>,
+    /// When the new request (or, for updates and appends, the new revision) expires.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct ShareTokenResponse {
+    pub uuid: Uuid,
+    pub share_token: String,
+}
+
+#[derive(Serialize)]
+pub struct ShareLinkCreatedResponse {
+    pub id: Uuid,
+    pub uuid: Uuid,
+    pub rev: Option<i32>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub views_remaining: Option<i32>,
+    /// Raw view, relative to the server root.
+    pub url: String,
+    /// Pretty view, relative to the server root.
+    pub view_url: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ShareLinkInfo {
+    pub id: Uuid,
+    pub rev: Option<i32>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub views_remaining: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct BlameResponse {
+    pub uuid: Uuid,
+    pub rev: i32,
+    pub content_type: String,
+    pub lines: Vec<BlameLine>,
+}
+
+#[derive(Serialize)]
+pub struct BlameLine {
+    pub line: usize,
+    /// Revision that introduced the line.
+    pub rev: i32,
+    pub created_at: Option<DateTime<Utc>>,
+    pub text: String,
+}
+
+#[derive(Serialize)]
+pub struct SimilarHint {
+    pub uuid: Uuid,
+    pub distance: i32,
+    pub message: String,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct ExcerptSource {
+    pub uuid: Uuid,
+    pub rev: i32,
+    pub sha256: String,
+    pub range_kind: String,
+    pub range_start: i32,
+    pub range_end: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct ExcerptCreatedResponse {
+    #[serde(flatten)]
+    pub request: RequestCreatedResponse,
+    pub source: ExcerptSource,
+}
+
+#[derive(Serialize)]
+pub struct PublicRequestMeta {
+    pub uuid: Uuid,
+    pub latest_rev: i32,
+    pub content_type: String,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub source: Option<ExcerptSource>,
+    pub parent: Option<RelationLink>,
+    pub children: Vec<RelationLink>,
+    pub commits: Vec<CommitMatch>,
+}
+
+/// A request in a relation tree; `kind` is its relation to the parent node.
+#[derive(Serialize)]
+pub struct RelatedNode {
+    pub uuid: Uuid,
+    pub kind: Option<String>,
+    pub title: Option<String>,
+    pub latest_rev: i32,
+    pub created_at: DateTime<Utc>,
+    pub children: Vec<RelatedNode>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct RelationLink {
+    pub uuid: Uuid,
+    pub kind: String,
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct CommitMatch {
+    pub uuid: Uuid,
+    pub sha: String,
+    pub rev: i32,
+    pub repo_url: Option<String>,
+    pub branch: Option<String>,
+    pub title: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 #[derive(Serialize, sqlx::FromRow)]
@@ -25,6 +221,48 @@ pub struct RequestListItem {
     pub updated_at: DateTime<Utc>,
     pub latest_rev: i32,
     pub latest_content_type: String,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub metadata: Json<Value>,
+    pub tags: Vec<String>,
+    pub visibility: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub share_token: Option<String>,
+    pub passphrase_protected: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub views_remaining: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct SearchResult {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub request: RequestListItem,
+    pub rank: f32,
+    pub snippet: String,
+}
+
+#[derive(Serialize)]
+pub struct RequestTags {
+    pub uuid: Uuid,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
+}
+
+#[derive(Serialize, sqlx::FromRow)]
+pub struct SimilarRequest {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub request: RequestListItem,
+    /// Number of differing bits between the two fingerprints (0-64).
+    pub distance: i32,
 }
 
 #[derive(Serialize, sqlx::FromRow)]
@@ -34,4 +272,38 @@ pub struct RevisionInfo {
     pub content_type: String,
     pub size_bytes: i32,
     pub sha256: String,
+    pub title: Option<String>,
+    pub summary: Option<String>,
+    pub metadata: Json<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub struct DiffResponse {
+    pub uuid: Uuid,
+    pub from: i32,
+    pub to: i32,
+    pub from_content_type: String,
+    pub to_content_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unified: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<MessageDiff>,
+}
+
+#[derive(Serialize)]
+pub struct SliceResponse {
+    pub uuid: Uuid,
+    pub rev: i32,
+    pub content_type: String,
+    pub total_lines: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_messages: Option<usize>,
+    pub start: usize,
+    pub count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lines: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub messages: Option<Vec<MessageEntry>>,
 }
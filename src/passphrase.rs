@@ -0,0 +1,153 @@
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::Mac;
+use rand::rngs::OsRng;
+use uuid::Uuid;
+
+use crate::{error::ApiError, util::hmac_sha256};
+
+/// Header that sets a passphrase on create, and presents one on public reads.
+pub const PASSPHRASE_HEADER: &str = "x-prompt-passphrase";
+pub const MIN_PASSPHRASE_CHARS: usize = 8;
+pub const MAX_PASSPHRASE_BYTES: usize = 1024;
+/// How long an unlock cookie is honoured.
+pub const UNLOCK_COOKIE_MAX_AGE_SECS: u64 = 24 * 3600;
+/// Context for the key unlock cookies are signed with, derived from the share link secret
+/// so a signature made for one can never pass as the other.
+const COOKIE_KEY_CONTEXT: &[u8] = b"prompt-request unlock cookie v1";
+/// Argon2 verifications that may run at once across all requests; further attempts get
+/// `429` rather than queueing for the blocking pool.
+pub const MAX_CONCURRENT_VERIFICATIONS: usize = 4;
+
+pub fn validate(passphrase: &str) -> Result<(), ApiError> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "passphrase must be at least {MIN_PASSPHRASE_CHARS} characters"
+        )));
+    }
+    if passphrase.len() > MAX_PASSPHRASE_BYTES {
+        return Err(ApiError::BadRequest(format!(
+            "passphrase must be at most {MAX_PASSPHRASE_BYTES} bytes"
+        )));
+    }
+    Ok(())
+}
+
+/// Argon2id hash in PHC string format. Hashing is deliberately slow, so this runs on the
+/// blocking pool.
+pub async fn hash(passphrase: String) -> Result<String, ApiError> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(passphrase.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| ApiError::Internal(format!("passphrase hashing failed: {e}")))
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.to_string()))?
+}
+
+pub async fn verify(hash: String, passphrase: String) -> Result<bool, ApiError> {
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&hash)
+            .map_err(|e| ApiError::Internal(format!("invalid passphrase hash: {e}")))?;
+        Ok(Argon2::default()
+            .verify_password(passphrase.as_bytes(), &parsed)
+            .is_ok())
+    })
+    .await
+    .map_err(|e| ApiError::Internal(e.to_string()))?
+}
+
+pub fn cookie_name(uuid: Uuid) -> String {
+    format!("prompt_unlock_{}", uuid.simple())
+}
+
+/// Value of the unlock cookie for a request, `<expires>.<signature>`. It is bound to the
+/// current hash, so changing or removing the passphrase invalidates cookies handed out for
+/// the old one, and it stops working at `expires` (unix seconds) wherever it ends up.
+pub fn cookie_value(secret: &str, uuid: Uuid, hash: &str, expires: i64) -> String {
+    let message = cookie_message(uuid, hash, expires);
+    let sig = hmac_sha256(&cookie_key(secret), &[message.as_bytes()]).finalize();
+    let sig = URL_SAFE_NO_PAD.encode(sig.into_bytes());
+    format!("{expires}.{sig}")
+}
+
+/// Checks an unlock cookie's signature and expiry.
+pub fn verify_cookie(secret: &str, uuid: Uuid, hash: &str, value: &str, now: i64) -> bool {
+    let Some((expires, sig)) = value.split_once('.') else {
+        return false;
+    };
+    let (Ok(expires), Ok(sig)) = (expires.parse::<i64>(), URL_SAFE_NO_PAD.decode(sig)) else {
+        return false;
+    };
+    let message = cookie_message(uuid, hash, expires);
+    expires > now
+        && hmac_sha256(&cookie_key(secret), &[message.as_bytes()])
+            .verify_slice(&sig)
+            .is_ok()
+}
+
+fn cookie_key(secret: &str) -> [u8; 32] {
+    hmac_sha256(secret.as_bytes(), &[COOKIE_KEY_CONTEXT])
+        .finalize()
+        .into_bytes()
+        .into()
+}
+
+/// What an unlock cookie's signature covers.
+fn cookie_message(uuid: Uuid, hash: &str, expires: i64) -> String {
+    format!("unlock:{uuid}:{hash}:{expires}")
+}
+
+/// Finds a cookie in a `Cookie` header.
+pub fn find_cookie<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+    header.split(';').find_map(|pair| {
+        let (key, value) = pair.trim().split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn hashes_and_verifies() {
+        let hash = hash("correct horse".to_string()).await.unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify(hash.clone(), "correct horse".to_string())
+            .await
+            .unwrap());
+        assert!(!verify(hash, "wrong horse".to_string()).await.unwrap());
+    }
+
+    #[test]
+    fn cookies() {
+        let uuid = Uuid::new_v4();
+        let name = cookie_name(uuid);
+        let value = cookie_value("secret", uuid, "$argon2id$a", 100);
+        assert!(verify_cookie("secret", uuid, "$argon2id$a", &value, 99));
+        assert!(!verify_cookie("secret", uuid, "$argon2id$a", &value, 100));
+        assert!(!verify_cookie("secret", uuid, "$argon2id$b", &value, 99));
+        assert!(!verify_cookie("other", uuid, "$argon2id$a", &value, 99));
+        let (_, sig) = value.split_once('.').unwrap();
+        let extended = format!("1000.{sig}");
+        assert!(!verify_cookie("secret", uuid, "$argon2id$a", &extended, 99));
+        assert!(!verify_cookie("secret", uuid, "$argon2id$a", "garbage", 99));
+        // Not keyed with the share link secret itself.
+        let raw = hmac_sha256(
+            b"secret",
+            &[cookie_message(uuid, "$argon2id$a", 100).as_bytes()],
+        );
+        assert_ne!(sig, URL_SAFE_NO_PAD.encode(raw.finalize().into_bytes()));
+        let header = format!("theme=dark; {name}={value}");
+        assert_eq!(find_cookie(&header, &name), Some(value.as_str()));
+        assert_eq!(find_cookie("theme=dark", &name), None);
+        assert!(validate("short").is_err());
+        assert!(validate("long enough").is_ok());
+    }
+}
@@ -0,0 +1,157 @@
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::{
+    error::ApiError,
+    models::{AccountUsage, Tier},
+};
+
+/// Columns of [`Tier`], for queries that alias `tiers` as `t`.
+pub const TIER_COLUMNS: &str =
+    "t.name, t.max_total_bytes, t.max_requests, t.max_upload_bytes, t.min_request_interval_ms";
+
+pub fn check_upload_size(tier: &Tier, len: usize) -> Result<(), ApiError> {
+    if len as i64 > tier.max_upload_bytes {
+        return Err(ApiError::PayloadTooLarge);
+    }
+    Ok(())
+}
+
+/// Checks usage after a charge of `bytes` and `requests`. Only the limits the charge adds
+/// to are checked, so an account moved to a smaller tier can still update and delete.
+pub fn check_limits(
+    tier: &Tier,
+    total_bytes: i64,
+    request_count: i64,
+    bytes: i64,
+    requests: i64,
+) -> Result<(), ApiError> {
+    if requests > 0 && request_count > tier.max_requests {
+        return Err(ApiError::QuotaExceeded(format!(
+            "the {} tier allows at most {} requests",
+            tier.name, tier.max_requests
+        )));
+    }
+    if bytes > 0 && total_bytes > tier.max_total_bytes {
+        return Err(ApiError::QuotaExceeded(format!(
+            "upload exceeds the {} tier's storage quota of {} bytes by {}",
+            tier.name,
+            tier.max_total_bytes,
+            total_bytes - tier.max_total_bytes
+        )));
+    }
+    Ok(())
+}
+
+/// Adds `bytes` and `requests` to the account's usage inside `tx`. The usage row stays
+/// locked until `tx` ends, so concurrent uploads see each other's charges; on error the
+/// caller drops `tx` and nothing is charged.
+pub async fn charge(
+    tx: &mut Transaction<'_, Postgres>,
+    account_id: i64,
+    tier: &Tier,
+    bytes: i64,
+    requests: i64,
+) -> Result<(), ApiError> {
+    let (total_bytes, request_count): (i64, i64) = sqlx::query_as(
+        "INSERT INTO account_usage (account_id, total_bytes, request_count) \
+         VALUES ($1, $2, $3) \
+         ON CONFLICT (account_id) DO UPDATE SET \
+             total_bytes = account_usage.total_bytes + EXCLUDED.total_bytes, \
+             request_count = account_usage.request_count + EXCLUDED.request_count \
+         RETURNING total_bytes, request_count",
+    )
+    .bind(account_id)
+    .bind(bytes)
+    .bind(requests)
+    .fetch_one(&mut **tx)
+    .await?;
+    check_limits(tier, total_bytes, request_count, bytes, requests)
+}
+
+/// Gives back what deleted revisions and requests were charged.
+pub async fn release(
+    tx: &mut Transaction<'_, Postgres>,
+    account_id: i64,
+    bytes: i64,
+    requests: i64,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE account_usage SET \
+             total_bytes = GREATEST(total_bytes - $2, 0), \
+             request_count = GREATEST(request_count - $3, 0) \
+         WHERE account_id = $1",
+    )
+    .bind(account_id)
+    .bind(bytes)
+    .bind(requests)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn usage(pool: &PgPool, account_id: i64, tier: Tier) -> Result<AccountUsage, ApiError> {
+    let (total_bytes, request_count): (i64, i64) = sqlx::query_as(
+        "SELECT total_bytes, request_count FROM account_usage WHERE account_id = $1",
+    )
+    .bind(account_id)
+    .fetch_optional(pool)
+    .await?
+    .unwrap_or((0, 0));
+    Ok(AccountUsage {
+        tier,
+        total_bytes,
+        request_count,
+    })
+}
+
+/// The tier of `account_id`, or `None` if the account does not exist.
+pub async fn account_tier(pool: &PgPool, account_id: i64) -> Result<Option<Tier>, ApiError> {
+    let tier = sqlx::query_as::<_, Tier>(&format!(
+        "SELECT {TIER_COLUMNS} FROM accounts a JOIN tiers t ON t.name = a.tier WHERE a.id = $1"
+    ))
+    .bind(account_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(tier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tier() -> Tier {
+        Tier {
+            name: "free".to_string(),
+            max_total_bytes: 100,
+            max_requests: 2,
+            max_upload_bytes: 10,
+            min_request_interval_ms: 1000,
+        }
+    }
+
+    #[test]
+    fn limits_apply_only_to_what_a_charge_adds() {
+        let tier = tier();
+        assert!(check_limits(&tier, 100, 2, 10, 1).is_ok());
+        assert!(matches!(
+            check_limits(&tier, 50, 3, 10, 1),
+            Err(ApiError::QuotaExceeded(_))
+        ));
+        assert!(matches!(
+            check_limits(&tier, 101, 1, 10, 0),
+            Err(ApiError::QuotaExceeded(_))
+        ));
+        // Over both limits after a downgrade, but an update that adds nothing still works.
+        assert!(check_limits(&tier, 500, 9, 0, 0).is_ok());
+    }
+
+    #[test]
+    fn upload_size_is_per_tier() {
+        let tier = tier();
+        assert!(check_upload_size(&tier, 10).is_ok());
+        assert!(matches!(
+            check_upload_size(&tier, 11),
+            Err(ApiError::PayloadTooLarge)
+        ));
+    }
+}
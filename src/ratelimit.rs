@@ -1,6 +1,6 @@
 use std::time::{Duration, Instant};
 
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
 
 use crate::error::ApiError;
 
@@ -19,17 +19,28 @@ impl RateLimiter {
     }
 
     pub fn check(&self, key: &str) -> Result<(), ApiError> {
+        self.check_within(key, self.window)
+    }
+
+    /// Like [`check`](Self::check), with a window chosen per call, e.g. from the account's tier.
+    /// The check and the hit happen under one lock, so concurrent calls cannot both pass.
+    pub fn check_within(&self, key: &str, window: Duration) -> Result<(), ApiError> {
         let now = Instant::now();
-        if let Some(entry) = self.entries.get(key) {
-            let elapsed = now.duration_since(*entry);
-            if elapsed < self.window {
-                let retry_after = (self.window - elapsed).as_secs().max(1);
-                return Err(ApiError::RateLimited {
-                    retry_after_secs: retry_after,
-                });
+        match self.entries.entry(key.to_string()) {
+            Entry::Occupied(mut entry) => {
+                let elapsed = now.duration_since(*entry.get());
+                if elapsed < window {
+                    let retry_after = (window - elapsed).as_secs().max(1);
+                    return Err(ApiError::RateLimited {
+                        retry_after_secs: retry_after,
+                    });
+                }
+                entry.insert(now);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(now);
             }
         }
-        self.entries.insert(key.to_string(), now);
         Ok(())
     }
 }
@@ -44,4 +55,29 @@ mod tests {
         assert!(limiter.check("a").is_ok());
         assert!(limiter.check("a").is_err());
     }
+
+    #[test]
+    fn window_can_vary_per_call() {
+        let limiter = RateLimiter::new(Duration::from_secs(1));
+        assert!(limiter.check_within("a", Duration::ZERO).is_ok());
+        assert!(limiter.check_within("a", Duration::ZERO).is_ok());
+        assert!(limiter.check("a").is_err());
+    }
+
+    #[test]
+    fn concurrent_checks_pass_once() {
+        let limiter = std::sync::Arc::new(RateLimiter::new(Duration::from_secs(60)));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let limiter = limiter.clone();
+                std::thread::spawn(move || limiter.check("a").is_ok())
+            })
+            .collect();
+        let passed = handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .filter(|ok| *ok)
+            .count();
+        assert_eq!(passed, 1);
+    }
 }
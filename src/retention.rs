@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::{error::ApiError, routes::requests, AppState};
+
+pub const EXPIRES_IN_HEADER: &str = "x-prompt-expires-in";
+
+/// How often the sweeper looks for expired requests and revisions.
+pub const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+/// Rows deleted per sweeper query; a sweep keeps going until a batch comes back short.
+pub const SWEEP_BATCH: i64 = 100;
+
+/// Validates a time-to-live in seconds against the instance maximum.
+pub fn validate_ttl(ttl: i64, max: Option<i64>) -> Result<i64, ApiError> {
+    let max = max.unwrap_or(i64::from(i32::MAX));
+    if !(1..=max).contains(&ttl) {
+        return Err(ApiError::BadRequest(format!(
+            "expires_in_secs must be between 1 and {max}"
+        )));
+    }
+    Ok(ttl)
+}
+
+/// Reads `X-Prompt-Expires-In`, if present.
+pub fn expires_in_from_headers(
+    headers: &HeaderMap,
+    max: Option<i64>,
+) -> Result<Option<i64>, ApiError> {
+    headers
+        .get(EXPIRES_IN_HEADER)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse().ok())
+                .ok_or_else(|| ApiError::BadRequest("invalid expires-in header".to_string()))
+                .and_then(|ttl| validate_ttl(ttl, max))
+        })
+        .transpose()
+}
+
+/// The time-to-live of a new request: the requested one, else the account default. Either
+/// is capped by the instance maximum, which also applies when neither is set.
+pub fn request_ttl(
+    requested: Option<i64>,
+    account_default: Option<i64>,
+    max: Option<i64>,
+) -> Option<i64> {
+    match (requested.or(account_default), max) {
+        (Some(ttl), Some(max)) => Some(ttl.min(max)),
+        (ttl, max) => ttl.or(max),
+    }
+}
+
+pub fn expires_at(ttl: Option<i64>) -> Option<DateTime<Utc>> {
+    ttl.map(|ttl| Utc::now() + chrono::Duration::seconds(ttl))
+}
+
+/// When a new request of `account_id` expires, given an optional requested TTL.
+pub async fn new_request_expiry(
+    state: &AppState,
+    account_id: i64,
+    requested: Option<i64>,
+) -> Result<Option<DateTime<Utc>>, ApiError> {
+    let account_default: Option<i64> =
+        sqlx::query_scalar("SELECT default_ttl_secs FROM accounts WHERE id = $1")
+            .bind(account_id)
+            .fetch_one(&state.pool)
+            .await?;
+    Ok(expires_at(request_ttl(
+        requested,
+        account_default,
+        state.max_ttl_secs,
+    )))
+}
+
+/// Deletes expired requests and revisions every [`SWEEP_INTERVAL`]. Reads already skip
+/// expired rows, so a slow or failed sweep only delays reclaiming storage.
+pub fn spawn_sweeper(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            match sweep(&state).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!(deleted, "swept expired requests and revisions"),
+                Err(err) => tracing::warn!(error = %err, "retention sweep failed"),
+            }
+        }
+    });
+}
+
+/// Runs one sweep and returns how many requests and revisions it deleted.
+pub async fn sweep(state: &AppState) -> Result<usize, ApiError> {
+    let mut deleted = 0;
+
+    loop {
+        let expired: Vec<(Uuid, i64)> = sqlx::query_as(
+            "SELECT uuid, account_id FROM requests \
+             WHERE expires_at <= now() \
+             ORDER BY expires_at \
+             LIMIT $1",
+        )
+        .bind(SWEEP_BATCH)
+        .fetch_all(&state.pool)
+        .await?;
+        for (uuid, account_id) in &expired {
+            ignore_missing(requests::delete_all(state, *uuid, *account_id).await)?;
+        }
+        deleted += expired.len();
+        if (expired.len() as i64) < SWEEP_BATCH {
+            break;
+        }
+    }
+
+    loop {
+        let expired: Vec<(Uuid, i64, i32)> = sqlx::query_as(
+            "SELECT rr.request_uuid, r.account_id, rr.rev_number \
+             FROM request_revisions rr \
+             JOIN requests r ON r.uuid = rr.request_uuid \
+             WHERE rr.expires_at <= now() \
+             ORDER BY rr.expires_at \
+             LIMIT $1",
+        )
+        .bind(SWEEP_BATCH)
+        .fetch_all(&state.pool)
+        .await?;
+        for (uuid, account_id, rev) in &expired {
+            ignore_missing(requests::delete_revision(state, *uuid, *account_id, *rev).await)?;
+        }
+        deleted += expired.len();
+        if (expired.len() as i64) < SWEEP_BATCH {
+            break;
+        }
+    }
+
+    Ok(deleted)
+}
+
+/// The owner may have deleted the row since it was selected.
+fn ignore_missing(res: Result<(), ApiError>) -> Result<(), ApiError> {
+    match res {
+        Err(ApiError::NotFound) => Ok(()),
+        res => res,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_ttl_prefers_request_then_default_capped_by_max() {
+        assert_eq!(request_ttl(None, None, None), None);
+        assert_eq!(request_ttl(Some(60), Some(3600), None), Some(60));
+        assert_eq!(request_ttl(None, Some(3600), None), Some(3600));
+        assert_eq!(request_ttl(None, Some(3600), Some(600)), Some(600));
+        assert_eq!(request_ttl(None, None, Some(600)), Some(600));
+        assert_eq!(request_ttl(Some(60), None, Some(600)), Some(60));
+    }
+
+    #[test]
+    fn validates_ttl_against_max() {
+        assert!(validate_ttl(0, None).is_err());
+        assert_eq!(validate_ttl(600, Some(600)).unwrap(), 600);
+        assert!(validate_ttl(601, Some(600)).is_err());
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(expires_in_from_headers(&headers, None).unwrap(), None);
+        headers.insert(EXPIRES_IN_HEADER, "3600".parse().unwrap());
+        assert_eq!(expires_in_from_headers(&headers, None).unwrap(), Some(3600));
+        headers.insert(EXPIRES_IN_HEADER, "soon".parse().unwrap());
+        assert!(expires_in_from_headers(&headers, None).is_err());
+    }
+}
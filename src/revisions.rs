@@ -0,0 +1,133 @@
+use bytes::{Bytes, BytesMut};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{error::ApiError, AppState};
+
+/// Appended revisions are compacted into a single object once they would span more segments
+/// than this, which bounds the number of object reads per revision.
+pub const MAX_SEGMENTS: usize = 64;
+
+#[derive(sqlx::FromRow)]
+struct RevisionRow {
+    rev: i32,
+    content_type: String,
+    sha256: String,
+    segment_keys: Vec<String>,
+    segment_bytes: Vec<i64>,
+    front_matter_bytes: i32,
+}
+
+pub struct RevisionContent {
+    pub rev: i32,
+    pub content_type: String,
+    pub sha256: String,
+    pub segment_keys: Vec<String>,
+    /// Size of each object in `segment_keys`.
+    pub segment_bytes: Vec<i64>,
+    /// Length of the Markdown front matter block at the start of `bytes`, if any.
+    pub front_matter_bytes: usize,
+    pub bytes: Bytes,
+}
+
+impl RevisionContent {
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.bytes).into_owned()
+    }
+
+    /// The body without its front matter block.
+    pub fn text_without_front_matter(&self) -> String {
+        let start = self.front_matter_bytes.min(self.bytes.len());
+        String::from_utf8_lossy(&self.bytes[start..]).into_owned()
+    }
+}
+
+/// Loads a revision (or the latest one when `rev` is `None`) together with its object bytes.
+/// Expired requests and revisions count as missing, so until the sweeper catches up the
+/// latest revision is the newest unexpired one.
+pub async fn fetch(
+    state: &AppState,
+    uuid: Uuid,
+    rev: Option<i32>,
+) -> Result<RevisionContent, ApiError> {
+    let row = if let Some(rev) = rev {
+        if rev < 1 {
+            return Err(ApiError::BadRequest("rev must be >= 1".to_string()));
+        }
+        sqlx::query_as::<_, RevisionRow>(
+            "SELECT rr.rev_number as rev, rr.content_type, rr.sha256, rr.segment_keys, \
+                    rr.segment_bytes, rr.front_matter_bytes \
+             FROM request_revisions rr \
+             JOIN requests r ON r.uuid = rr.request_uuid \
+             WHERE r.uuid = $1 AND rr.rev_number = $2 \
+               AND (r.expires_at IS NULL OR r.expires_at > now()) \
+               AND (rr.expires_at IS NULL OR rr.expires_at > now())",
+        )
+        .bind(uuid)
+        .bind(rev)
+        .fetch_optional(&state.pool)
+        .await?
+    } else {
+        sqlx::query_as::<_, RevisionRow>(
+            "SELECT rr.rev_number as rev, rr.content_type, rr.sha256, rr.segment_keys, \
+                    rr.segment_bytes, rr.front_matter_bytes \
+             FROM request_revisions rr \
+             JOIN requests r ON r.uuid = rr.request_uuid \
+             WHERE r.uuid = $1 AND rr.rev_number <= r.latest_rev \
+               AND (r.expires_at IS NULL OR r.expires_at > now()) \
+               AND (rr.expires_at IS NULL OR rr.expires_at > now()) \
+             ORDER BY rr.rev_number DESC \
+             LIMIT 1",
+        )
+        .bind(uuid)
+        .fetch_optional(&state.pool)
+        .await?
+    }
+    .ok_or(ApiError::NotFound)?;
+
+    let bytes = read_segments(state, &row.segment_keys).await?;
+
+    Ok(RevisionContent {
+        rev: row.rev,
+        content_type: row.content_type,
+        sha256: row.sha256,
+        segment_keys: row.segment_keys,
+        segment_bytes: row.segment_bytes,
+        front_matter_bytes: row.front_matter_bytes.max(0) as usize,
+        bytes,
+    })
+}
+
+/// Concatenates the objects that make up a revision.
+pub async fn read_segments(state: &AppState, keys: &[String]) -> Result<Bytes, ApiError> {
+    if let [key] = keys {
+        return state.store.get(key).await;
+    }
+
+    let mut buf = BytesMut::new();
+    for key in keys {
+        buf.extend_from_slice(&state.store.get(key).await?);
+    }
+    Ok(buf.freeze())
+}
+
+/// Returns the subset of `keys` that no remaining revision of `uuid` refers to, i.e. the
+/// objects that are safe to delete once the transaction commits.
+pub async fn unreferenced_keys(
+    tx: &mut Transaction<'_, Postgres>,
+    uuid: Uuid,
+    keys: &[String],
+) -> Result<Vec<String>, ApiError> {
+    let keys = sqlx::query_scalar(
+        "SELECT DISTINCT k FROM unnest($1::text[]) AS k \
+         WHERE NOT EXISTS ( \
+             SELECT 1 FROM request_revisions \
+             WHERE request_uuid = $2 AND k = ANY(segment_keys) \
+         )",
+    )
+    .bind(keys)
+    .bind(uuid)
+    .fetch_all(&mut **tx)
+    .await?;
+    Ok(keys)
+}
@@ -1,10 +1,16 @@
 use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde::Deserialize;
 
 use crate::{
-    auth::ClientIp,
+    auth::{AuthContext, ClientIp},
     error::ApiError,
-    models::CreateAccountResponse,
-    util::{generate_api_key, hash_api_key},
+    models::{AccountSettings, AccountUsage, CreateAccountResponse},
+    quota, retention,
+    routes::{
+        keys::{self, DEFAULT_KEY_NAME},
+        requests::present,
+    },
+    scopes::Scopes,
     AppState,
 };
 
@@ -17,19 +23,84 @@ pub async fn create_account(
 ) -> Result<impl IntoResponse, ApiError> {
     state.account_create_limiter.check(&ip.to_string())?;
 
-    let api_key = generate_api_key();
-    let hash = hash_api_key(&api_key, state.api_key_pepper.as_deref());
-
-    sqlx::query("INSERT INTO accounts (api_key_hash) VALUES ($1)")
-        .bind(hash)
-        .execute(&state.pool)
+    let mut tx = state.pool.begin().await?;
+    let account_id: i64 = sqlx::query_scalar("INSERT INTO accounts DEFAULT VALUES RETURNING id")
+        .fetch_one(&mut *tx)
         .await?;
+    let key = keys::insert_key(
+        &mut tx,
+        &state.key_hasher,
+        account_id,
+        DEFAULT_KEY_NAME,
+        None,
+        &Scopes::full(),
+    )
+    .await?;
+    tx.commit().await?;
 
     Ok((
         StatusCode::CREATED,
         Json(CreateAccountResponse {
-            api_key,
+            api_key: key.api_key,
             comment_for_model: COMMENT_FOR_MODEL.to_string(),
         }),
     ))
 }
+
+/// `null` clears the default; an omitted field is left unchanged.
+#[derive(Deserialize)]
+pub struct PatchAccount {
+    #[serde(default, deserialize_with = "present")]
+    pub default_ttl_secs: Option<Option<i64>>,
+}
+
+pub async fn get_account(
+    State(state): State<AppState>,
+    auth: AuthContext,
+) -> Result<Json<AccountSettings>, ApiError> {
+    let settings = sqlx::query_as::<_, AccountSettings>(
+        "SELECT created_at, tier, default_ttl_secs FROM accounts WHERE id = $1",
+    )
+    .bind(auth.account_id)
+    .fetch_one(&state.pool)
+    .await?;
+    Ok(Json(settings))
+}
+
+pub async fn patch_account(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Json(req): Json<PatchAccount>,
+) -> Result<Json<AccountSettings>, ApiError> {
+    auth.require_full_key()?;
+    let default_ttl = req
+        .default_ttl_secs
+        .map(|ttl| {
+            ttl.map(|ttl| retention::validate_ttl(ttl, state.max_ttl_secs))
+                .transpose()
+        })
+        .transpose()?;
+
+    let settings = sqlx::query_as::<_, AccountSettings>(
+        "UPDATE accounts SET \
+             default_ttl_secs = CASE WHEN $2 THEN $3 ELSE default_ttl_secs END \
+         WHERE id = $1 \
+         RETURNING created_at, tier, default_ttl_secs",
+    )
+    .bind(auth.account_id)
+    .bind(default_ttl.is_some())
+    .bind(default_ttl.flatten())
+    .fetch_one(&state.pool)
+    .await?;
+    Ok(Json(settings))
+}
+
+/// The account's tier and how much of it is used.
+pub async fn get_usage(
+    State(state): State<AppState>,
+    auth: AuthContext,
+) -> Result<Json<AccountUsage>, ApiError> {
+    Ok(Json(
+        quota::usage(&state.pool, auth.account_id, auth.tier).await?,
+    ))
+}
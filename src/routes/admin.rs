@@ -0,0 +1,132 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::{
+    auth::AdminAuth,
+    error::ApiError,
+    models::{AccountUsage, Tier},
+    quota::{self, TIER_COLUMNS},
+    util::MAX_BODY_BYTES,
+    AppState,
+};
+
+const MAX_TIER_NAME_CHARS: usize = 32;
+
+#[derive(Deserialize)]
+pub struct PutTier {
+    pub max_total_bytes: i64,
+    pub max_requests: i64,
+    pub max_upload_bytes: i64,
+    pub min_request_interval_ms: i64,
+}
+
+#[derive(Deserialize)]
+pub struct SetAccountTier {
+    pub tier: String,
+}
+
+pub async fn list_tiers(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+) -> Result<Json<Vec<Tier>>, ApiError> {
+    let tiers = sqlx::query_as::<_, Tier>(&format!(
+        "SELECT {TIER_COLUMNS} FROM tiers t ORDER BY t.max_total_bytes, t.name"
+    ))
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(tiers))
+}
+
+/// Creates or replaces a tier. Accounts on it pick up the new limits on their next call.
+pub async fn put_tier(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    Path(name): Path<String>,
+    Json(req): Json<PutTier>,
+) -> Result<Json<Tier>, ApiError> {
+    validate_tier_name(&name)?;
+    if req.max_total_bytes < 1 || req.max_requests < 1 || req.max_upload_bytes < 1 {
+        return Err(ApiError::BadRequest(
+            "tier limits must be positive".to_string(),
+        ));
+    }
+    if req.max_upload_bytes > MAX_BODY_BYTES as i64 {
+        return Err(ApiError::BadRequest(format!(
+            "max_upload_bytes cannot exceed {MAX_BODY_BYTES}"
+        )));
+    }
+    if req.min_request_interval_ms < 0 {
+        return Err(ApiError::BadRequest(
+            "min_request_interval_ms must be >= 0".to_string(),
+        ));
+    }
+
+    let tier = sqlx::query_as::<_, Tier>(
+        "INSERT INTO tiers AS t \
+             (name, max_total_bytes, max_requests, max_upload_bytes, min_request_interval_ms) \
+         VALUES ($1, $2, $3, $4, $5) \
+         ON CONFLICT (name) DO UPDATE SET \
+             max_total_bytes = EXCLUDED.max_total_bytes, \
+             max_requests = EXCLUDED.max_requests, \
+             max_upload_bytes = EXCLUDED.max_upload_bytes, \
+             min_request_interval_ms = EXCLUDED.min_request_interval_ms \
+         RETURNING t.name, t.max_total_bytes, t.max_requests, t.max_upload_bytes, \
+             t.min_request_interval_ms",
+    )
+    .bind(&name)
+    .bind(req.max_total_bytes)
+    .bind(req.max_requests)
+    .bind(req.max_upload_bytes)
+    .bind(req.min_request_interval_ms)
+    .fetch_one(&state.pool)
+    .await?;
+    Ok(Json(tier))
+}
+
+/// Moves an account to another tier. Usage above the new limits is kept; the account just
+/// cannot add more until it is back under them.
+pub async fn set_account_tier(
+    State(state): State<AppState>,
+    _admin: AdminAuth,
+    Path(account_id): Path<i64>,
+    Json(req): Json<SetAccountTier>,
+) -> Result<Json<AccountUsage>, ApiError> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM tiers WHERE name = $1)")
+        .bind(&req.tier)
+        .fetch_one(&state.pool)
+        .await?;
+    if !exists {
+        return Err(ApiError::BadRequest(format!("unknown tier: {}", req.tier)));
+    }
+
+    let updated = sqlx::query("UPDATE accounts SET tier = $1 WHERE id = $2")
+        .bind(&req.tier)
+        .bind(account_id)
+        .execute(&state.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    let tier = quota::account_tier(&state.pool, account_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(quota::usage(&state.pool, account_id, tier).await?))
+}
+
+fn validate_tier_name(name: &str) -> Result<(), ApiError> {
+    let valid = !name.is_empty()
+        && name.chars().count() <= MAX_TIER_NAME_CHARS
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    if !valid {
+        return Err(ApiError::BadRequest(format!(
+            "tier names are 1-{MAX_TIER_NAME_CHARS} characters of a-z, 0-9, - and _"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{access::Viewer, auth::ClientIp, error::ApiError, models::CommitMatch, AppState};
+
+pub const REPO_HEADER: &str = "x-prompt-git-repo";
+pub const COMMIT_HEADER: &str = "x-prompt-git-commit";
+pub const BRANCH_HEADER: &str = "x-prompt-git-branch";
+
+pub const MIN_SHA_PREFIX: usize = 7;
+const MAX_COMMITS_PER_UPLOAD: usize = 100;
+const MAX_REPO_LEN: usize = 512;
+const MAX_BRANCH_LEN: usize = 255;
+const MAX_MATCHES: i64 = 50;
+
+/// Git context declared by an upload.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct GitInfo {
+    pub repo: Option<String>,
+    pub branch: Option<String>,
+    pub commits: Vec<String>,
+}
+
+impl GitInfo {
+    /// Reads `X-Prompt-Git-*` headers, falling back to the `git_repo`, `git_branch` and
+    /// `git_commit`/`git_commits` metadata fields. Commit headers may repeat or hold a
+    /// comma-separated list; commit fields may be a string or an array of strings.
+    pub fn from_upload(
+        headers: &HeaderMap,
+        metadata: &Map<String, Value>,
+    ) -> Result<Self, ApiError> {
+        let header_values = |name: &str| -> Result<Vec<String>, ApiError> {
+            headers
+                .get_all(name)
+                .iter()
+                .map(|v| {
+                    v.to_str()
+                        .map(str::to_string)
+                        .map_err(|_| ApiError::BadRequest(format!("invalid header {name}")))
+                })
+                .collect()
+        };
+        let meta_str = |key: &str| -> Result<Option<String>, ApiError> {
+            match metadata.get(key) {
+                None | Some(Value::Null) => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(_) => Err(ApiError::BadRequest(format!("{key} must be a string"))),
+            }
+        };
+
+        let repo = match header_values(REPO_HEADER)?.pop() {
+            Some(repo) => Some(repo),
+            None => meta_str("git_repo")?,
+        };
+        let branch = match header_values(BRANCH_HEADER)?.pop() {
+            Some(branch) => Some(branch),
+            None => meta_str("git_branch")?,
+        };
+
+        let mut raw_commits = Vec::new();
+        for value in header_values(COMMIT_HEADER)? {
+            raw_commits.extend(value.split(',').map(str::to_string));
+        }
+        for key in ["git_commit", "git_commits"] {
+            match metadata.get(key) {
+                None | Some(Value::Null) => {}
+                Some(Value::String(s)) => raw_commits.push(s.clone()),
+                Some(Value::Array(items)) => {
+                    for item in items {
+                        let sha = item.as_str().ok_or_else(|| {
+                            ApiError::BadRequest(format!("{key} must contain strings"))
+                        })?;
+                        raw_commits.push(sha.to_string());
+                    }
+                }
+                Some(_) => {
+                    return Err(ApiError::BadRequest(format!(
+                        "{key} must be a string or an array of strings"
+                    )))
+                }
+            }
+        }
+
+        let mut commits: Vec<String> = Vec::new();
+        for raw in raw_commits {
+            let sha = normalize_sha(&raw, false)?;
+            if !commits.contains(&sha) {
+                commits.push(sha);
+            }
+        }
+        if commits.len() > MAX_COMMITS_PER_UPLOAD {
+            return Err(ApiError::BadRequest(format!(
+                "at most {MAX_COMMITS_PER_UPLOAD} commits per upload"
+            )));
+        }
+
+        let repo = repo.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+        let branch = branch
+            .map(|b| b.trim().to_string())
+            .filter(|b| !b.is_empty());
+        if repo.as_ref().is_some_and(|r| r.len() > MAX_REPO_LEN) {
+            return Err(ApiError::BadRequest("git repo URL is too long".to_string()));
+        }
+        if branch.as_ref().is_some_and(|b| b.len() > MAX_BRANCH_LEN) {
+            return Err(ApiError::BadRequest("git branch is too long".to_string()));
+        }
+
+        Ok(Self {
+            repo,
+            branch,
+            commits,
+        })
+    }
+}
+
+/// Lowercases a commit SHA. Declared commits must be full SHA-1 or SHA-256 hashes; lookups
+/// also accept abbreviations of at least `MIN_SHA_PREFIX` characters.
+pub fn normalize_sha(raw: &str, allow_prefix: bool) -> Result<String, ApiError> {
+    let sha = raw.trim().to_ascii_lowercase();
+    let valid_len = if allow_prefix {
+        (MIN_SHA_PREFIX..=64).contains(&sha.len())
+    } else {
+        sha.len() == 40 || sha.len() == 64
+    };
+    if !valid_len || !sha.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ApiError::BadRequest(format!("invalid commit sha: {raw}")));
+    }
+    Ok(sha)
+}
+
+/// Stores the commits declared by revision `rev`. Commits already linked to the request
+/// keep the revision that first declared them.
+pub async fn record(
+    tx: &mut Transaction<'_, Postgres>,
+    uuid: Uuid,
+    rev: i32,
+    git: &GitInfo,
+) -> Result<(), ApiError> {
+    if git.commits.is_empty() {
+        return Ok(());
+    }
+    sqlx::query(
+        "INSERT INTO request_commits (request_uuid, sha, rev, repo_url, branch) \
+         SELECT $1, unnest($2::text[]), $3, $4, $5 \
+         ON CONFLICT (request_uuid, sha) DO NOTHING",
+    )
+    .bind(uuid)
+    .bind(&git.commits)
+    .bind(rev)
+    .bind(&git.repo)
+    .bind(&git.branch)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct CommitQuery {
+    pub repo: Option<String>,
+}
+
+/// Public lookup of the requests that declared a commit, by full or abbreviated SHA.
+pub async fn lookup(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    viewer: Viewer,
+    Path(sha): Path<String>,
+    Query(q): Query<CommitQuery>,
+) -> Result<Json<Vec<CommitMatch>>, ApiError> {
+    state.public_read_limiter.check(&ip.to_string())?;
+    let sha = normalize_sha(&sha, true)?;
+
+    let rows = sqlx::query_as::<_, CommitMatch>(
+        "SELECT c.request_uuid AS uuid, c.sha, c.rev, c.repo_url, c.branch, \
+                COALESCE(r.title_override, r.title) AS title, c.created_at \
+         FROM request_commits c \
+         JOIN requests r ON r.uuid = c.request_uuid \
+         WHERE c.sha LIKE $1 || '%' \
+           AND ($2::text IS NULL OR c.repo_url = $2) \
+           AND ((r.visibility = 'public' AND r.passphrase_hash IS NULL \
+                 AND r.views_remaining IS NULL) OR r.account_id = $4) \
+           AND (r.expires_at IS NULL OR r.expires_at > now()) \
+         ORDER BY c.created_at DESC \
+         LIMIT $3",
+    )
+    .bind(&sha)
+    .bind(q.repo.as_deref())
+    .bind(MAX_MATCHES)
+    .bind(viewer.reader_account())
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+/// Commits declared by a request, for its public metadata.
+pub async fn for_request(state: &AppState, uuid: Uuid) -> Result<Vec<CommitMatch>, ApiError> {
+    let rows = sqlx::query_as::<_, CommitMatch>(
+        "SELECT c.request_uuid AS uuid, c.sha, c.rev, c.repo_url, c.branch, \
+                COALESCE(r.title_override, r.title) AS title, c.created_at \
+         FROM request_commits c \
+         JOIN requests r ON r.uuid = c.request_uuid \
+         WHERE c.request_uuid = $1 \
+         ORDER BY c.created_at, c.sha",
+    )
+    .bind(uuid)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA: &str = "3f786850e387550fdab836ed7e6dc881de23001b";
+
+    #[test]
+    fn headers_and_metadata_are_combined() {
+        let mut headers = HeaderMap::new();
+        headers.insert(REPO_HEADER, "https://github.com/acme/app".parse().unwrap());
+        headers.append(COMMIT_HEADER, SHA.to_uppercase().parse().unwrap());
+        let other = "a".repeat(40);
+        let meta = serde_json::json!({
+            "git_repo": "ignored",
+            "git_branch": "main",
+            "git_commits": [SHA, other],
+        });
+        let git = GitInfo::from_upload(&headers, meta.as_object().unwrap()).unwrap();
+        assert_eq!(git.repo.as_deref(), Some("https://github.com/acme/app"));
+        assert_eq!(git.branch.as_deref(), Some("main"));
+        assert_eq!(git.commits, vec![SHA.to_string(), other]);
+    }
+
+    #[test]
+    fn validates_shas() {
+        assert!(normalize_sha("3f78685", false).is_err());
+        assert_eq!(normalize_sha("3F78685", true).unwrap(), "3f78685");
+        assert!(normalize_sha("3f786", true).is_err());
+        assert!(normalize_sha(&"g".repeat(40), false).is_err());
+    }
+}
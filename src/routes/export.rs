@@ -0,0 +1,232 @@
+use std::io;
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue,
+    },
+    response::Response,
+};
+use axum_extra::extract::Query as MultiQuery;
+use bytes::Bytes;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthContext,
+    dataset::{self, ArchiveWriter, DatasetFormat, Manifest, ManifestEntry},
+    error::ApiError,
+    revisions,
+    routes::tags::{self, TagMode},
+    scopes::Scope,
+    util::ContentKind,
+    AppState,
+};
+
+/// Requests are read in pages of this size while streaming.
+const PAGE_SIZE: i64 = 100;
+const MAX_EXPORT_REQUESTS: usize = 10_000;
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    pub format: Option<String>,
+    pub archive: Option<String>,
+    #[serde(default)]
+    pub tag: Vec<String>,
+    pub tag_mode: Option<String>,
+    pub since: Option<String>,
+    pub until: Option<String>,
+}
+
+struct ExportFilter {
+    account_id: i64,
+    tags: Vec<String>,
+    required_tags: i64,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+/// Streams the latest JSONL revision of each matching request as training data. A request
+/// whose latest revision has expired is exported from the newest one that has not.
+pub async fn export(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    MultiQuery(q): MultiQuery<ExportQuery>,
+) -> Result<Response, ApiError> {
+    auth.scopes.require_all(Scope::Read)?;
+    let format = DatasetFormat::parse(q.format.as_deref())?;
+    let tar_gz = match q.archive.as_deref() {
+        None | Some("jsonl") => false,
+        Some("tar.gz") | Some("tgz") => true,
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "unsupported archive: {other}"
+            )))
+        }
+    };
+    let tags = tags::normalize_all(&q.tag)?;
+    let required_tags = match TagMode::parse(q.tag_mode.as_deref())? {
+        TagMode::All => tags.len() as i64,
+        TagMode::Any => 1,
+    };
+    let filter = ExportFilter {
+        account_id: auth.account_id,
+        tags,
+        required_tags,
+        since: q.since.as_deref().map(parse_time).transpose()?,
+        until: q.until.as_deref().map(parse_time).transpose()?,
+    };
+
+    let (tx, mut rx) = mpsc::channel::<io::Result<Bytes>>(8);
+    tokio::spawn(async move {
+        if let Err(err) = stream_export(&state, &filter, format, tar_gz, &tx).await {
+            tracing::warn!(error = %err, "export failed");
+            let _ = tx.send(Err(io::Error::other(err.to_string()))).await;
+        }
+    });
+    let stream = futures::stream::poll_fn(move |cx| rx.poll_recv(cx));
+
+    let (content_type, extension) = if tar_gz {
+        ("application/gzip", "tar.gz")
+    } else {
+        (ContentKind::Jsonl.canonical_type(), "jsonl")
+    };
+    let mut resp = Response::new(Body::from_stream(stream));
+    let headers = resp.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Ok(value) = HeaderValue::from_str(&format!(
+        "attachment; filename=\"export-{}.{extension}\"",
+        format.as_str()
+    )) {
+        headers.insert(CONTENT_DISPOSITION, value);
+    }
+    Ok(resp)
+}
+
+#[derive(sqlx::FromRow)]
+struct ExportRow {
+    uuid: Uuid,
+    created_at: DateTime<Utc>,
+    /// The newest unexpired revision, the one reads of the latest serve.
+    rev: i32,
+}
+
+async fn stream_export(
+    state: &AppState,
+    filter: &ExportFilter,
+    format: DatasetFormat,
+    tar_gz: bool,
+    tx: &mpsc::Sender<io::Result<Bytes>>,
+) -> Result<(), ApiError> {
+    let mut writer = ArchiveWriter::new(tar_gz);
+    let mut manifest = Manifest {
+        format: format.as_str(),
+        exported_at: Utc::now(),
+        requests: Vec::new(),
+        skipped: Vec::new(),
+        truncated: false,
+    };
+    let io_err = |e: io::Error| ApiError::Internal(format!("export encoding failed: {e}"));
+
+    let mut cursor: Option<(DateTime<Utc>, Uuid)> = None;
+    let mut seen = 0;
+    'pages: loop {
+        let rows = sqlx::query_as::<_, ExportRow>(
+            "SELECT r.uuid, r.created_at, rr.rev_number AS rev \
+             FROM requests r \
+             JOIN LATERAL ( \
+                 SELECT rev_number, content_type \
+                 FROM request_revisions \
+                 WHERE request_uuid = r.uuid AND rev_number <= r.latest_rev \
+                   AND (expires_at IS NULL OR expires_at > now()) \
+                 ORDER BY rev_number DESC \
+                 LIMIT 1 \
+             ) rr ON true \
+             WHERE r.account_id = $1 \
+               AND rr.content_type = $2 \
+               AND (r.expires_at IS NULL OR r.expires_at > now()) \
+               AND (cardinality($3::text[]) = 0 OR ( \
+                   SELECT COUNT(*) FROM request_tags t \
+                   WHERE t.request_uuid = r.uuid AND t.tag = ANY($3) \
+               ) >= $4) \
+               AND ($5::timestamptz IS NULL OR r.created_at >= $5) \
+               AND ($6::timestamptz IS NULL OR r.created_at < $6) \
+               AND ($7::timestamptz IS NULL OR (r.created_at, r.uuid) > ($7, $8)) \
+             ORDER BY r.created_at, r.uuid \
+             LIMIT $9",
+        )
+        .bind(filter.account_id)
+        .bind(ContentKind::Jsonl.canonical_type())
+        .bind(&filter.tags)
+        .bind(filter.required_tags)
+        .bind(filter.since)
+        .bind(filter.until)
+        .bind(cursor.map(|(created_at, _)| created_at))
+        .bind(cursor.map(|(_, uuid)| uuid))
+        .bind(PAGE_SIZE)
+        .fetch_all(&state.pool)
+        .await?;
+
+        let Some(last) = rows.last() else {
+            break;
+        };
+        cursor = Some((last.created_at, last.uuid));
+
+        for row in rows {
+            if seen == MAX_EXPORT_REQUESTS {
+                manifest.truncated = true;
+                break 'pages;
+            }
+            seen += 1;
+
+            // A request or revision deleted since the page was read is simply left out.
+            let revision = match revisions::fetch(state, row.uuid, Some(row.rev)).await {
+                Ok(revision) => revision,
+                Err(ApiError::NotFound) => continue,
+                Err(err) => return Err(err),
+            };
+            let mut entry = ManifestEntry {
+                uuid: row.uuid,
+                rev: revision.rev,
+                sha256: revision.sha256.clone(),
+                file: None,
+                line: None,
+            };
+            let Some(record) = dataset::convert(format, row.uuid, &revision.text()) else {
+                manifest.skipped.push(entry);
+                continue;
+            };
+            entry.file = writer.file_name(row.uuid);
+            entry.line = writer.next_line();
+            manifest.requests.push(entry);
+
+            let chunk = writer.record(row.uuid, &record).map_err(io_err)?;
+            if !chunk.is_empty() && tx.send(Ok(chunk)).await.is_err() {
+                // The client went away.
+                return Ok(());
+            }
+        }
+    }
+
+    let tail = writer.finish(&manifest).map_err(io_err)?;
+    if !tail.is_empty() {
+        let _ = tx.send(Ok(tail)).await;
+    }
+    Ok(())
+}
+
+/// Accepts RFC 3339 timestamps or plain `YYYY-MM-DD` dates (midnight UTC).
+fn parse_time(raw: &str) -> Result<DateTime<Utc>, ApiError> {
+    if let Ok(ts) = DateTime::parse_from_rfc3339(raw) {
+        return Ok(ts.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|dt| dt.and_utc())
+        .ok_or_else(|| ApiError::BadRequest(format!("invalid date: {raw}")))
+}
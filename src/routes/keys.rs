@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthContext,
+    error::ApiError,
+    keyhash::KeyHasher,
+    models::{ApiKeyCreatedResponse, ApiKeyInfo},
+    retention,
+    scopes::Scopes,
+    util::generate_api_key,
+    AppState,
+};
+
+pub const MAX_KEY_NAME_CHARS: usize = 64;
+/// Active (unrevoked, unexpired) keys an account may hold at once.
+pub const MAX_ACTIVE_KEYS: i64 = 100;
+/// Name of the key minted with a new account.
+pub const DEFAULT_KEY_NAME: &str = "default";
+
+#[derive(Deserialize)]
+pub struct CreateKey {
+    pub name: String,
+    /// The key stops working after this many seconds; by default it never expires.
+    pub expires_in_secs: Option<i64>,
+    /// Defaults to every scope.
+    pub scopes: Option<Vec<String>>,
+    /// Limits the key to these requests; by default it can reach all of the account's.
+    pub request_uuids: Option<Vec<Uuid>>,
+}
+
+pub async fn create_key(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Json(req): Json<CreateKey>,
+) -> Result<(StatusCode, Json<ApiKeyCreatedResponse>), ApiError> {
+    auth.require_full_key()?;
+    let name = validate_key_name(&req.name)?;
+    let scopes = Scopes::from_request(req.scopes.as_deref(), req.request_uuids)?;
+    let expires_at = retention::expires_at(
+        req.expires_in_secs
+            .map(|ttl| retention::validate_ttl(ttl, None))
+            .transpose()?,
+    );
+
+    let mut tx = state.pool.begin().await?;
+    // Serializes key creation per account so the limit below holds.
+    sqlx::query("SELECT 1 FROM accounts WHERE id = $1 FOR UPDATE")
+        .bind(auth.account_id)
+        .execute(&mut *tx)
+        .await?;
+    let active: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM api_keys \
+         WHERE account_id = $1 AND revoked_at IS NULL \
+           AND (expires_at IS NULL OR expires_at > now())",
+    )
+    .bind(auth.account_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if active >= MAX_ACTIVE_KEYS {
+        return Err(ApiError::BadRequest(format!(
+            "an account can hold at most {MAX_ACTIVE_KEYS} active keys"
+        )));
+    }
+
+    let created = insert_key(
+        &mut tx,
+        &state.key_hasher,
+        auth.account_id,
+        name,
+        expires_at,
+        &scopes,
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok((StatusCode::CREATED, Json(created)))
+}
+
+pub async fn list_keys(
+    State(state): State<AppState>,
+    auth: AuthContext,
+) -> Result<Json<Vec<ApiKeyInfo>>, ApiError> {
+    auth.require_full_key()?;
+    let keys = sqlx::query_as::<_, ApiKeyInfo>(
+        "SELECT id, name, scopes, request_uuids, created_at, last_used_at, expires_at, \
+                revoked_at, id = $2 AS current \
+         FROM api_keys \
+         WHERE account_id = $1 \
+         ORDER BY created_at DESC",
+    )
+    .bind(auth.account_id)
+    .bind(auth.key_id)
+    .fetch_all(&state.pool)
+    .await?;
+    Ok(Json(keys))
+}
+
+/// Revokes a key at once. The account's last active key cannot be revoked, since the
+/// account would be unreachable without it.
+pub async fn revoke_key(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(id): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    auth.require_full_key()?;
+    let mut tx = state.pool.begin().await?;
+
+    let active: Vec<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM api_keys \
+         WHERE account_id = $1 AND revoked_at IS NULL \
+           AND (expires_at IS NULL OR expires_at > now()) \
+         FOR UPDATE",
+    )
+    .bind(auth.account_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    if active == [id] {
+        return Err(ApiError::Conflict(
+            "cannot revoke the account's last active key".to_string(),
+        ));
+    }
+
+    let res = sqlx::query(
+        "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, now()) \
+         WHERE id = $1 AND account_id = $2",
+    )
+    .bind(id)
+    .bind(auth.account_id)
+    .execute(&mut *tx)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    tx.commit().await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Mints a key for `account_id`. Only its hash is stored.
+pub(crate) async fn insert_key(
+    tx: &mut Transaction<'_, Postgres>,
+    hasher: &KeyHasher,
+    account_id: i64,
+    name: &str,
+    expires_at: Option<DateTime<Utc>>,
+    scopes: &Scopes,
+) -> Result<ApiKeyCreatedResponse, ApiError> {
+    let id = Uuid::new_v4();
+    let api_key = generate_api_key();
+    let created_at = sqlx::query_scalar(
+        "INSERT INTO api_keys (id, account_id, key_hash, name, expires_at, scopes, request_uuids) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7) \
+         RETURNING created_at",
+    )
+    .bind(id)
+    .bind(account_id)
+    .bind(hasher.hash(&api_key))
+    .bind(name)
+    .bind(expires_at)
+    .bind(scopes.names())
+    .bind(scopes.requests())
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(ApiKeyCreatedResponse {
+        id,
+        name: name.to_string(),
+        api_key,
+        scopes: scopes.names(),
+        request_uuids: scopes.requests().map(<[Uuid]>::to_vec),
+        created_at,
+        expires_at,
+    })
+}
+
+fn validate_key_name(name: &str) -> Result<&str, ApiError> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_KEY_NAME_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "key names must be 1-{MAX_KEY_NAME_CHARS} characters"
+        )));
+    }
+    Ok(name)
+}
@@ -1,3 +1,12 @@
 pub mod accounts;
+pub mod admin;
+pub mod commits;
+pub mod export;
+pub mod keys;
 pub mod public;
+pub mod relations;
 pub mod requests;
+pub mod sharing;
+pub mod similar;
+pub mod tags;
+pub mod tokens;
@@ -1,23 +1,51 @@
 use axum::{
     body::Body,
     extract::{Path, Query, State},
-    http::{header::CONTENT_TYPE, HeaderValue},
-    response::Response,
+    http::{
+        header::{CONTENT_TYPE, SET_COOKIE},
+        HeaderValue, StatusCode,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+    Json,
 };
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream};
 use serde::Deserialize;
+use tokio::{sync::broadcast::error::RecvError, time::Instant};
 use uuid::Uuid;
 
-use crate::{auth::ClientIp, error::ApiError, util::ContentKind, AppState};
+use crate::{
+    access::Viewer,
+    auth::ClientIp,
+    diff::{self, DiffQuery},
+    error::ApiError,
+    events::{EventMessage, RevisionEvent},
+    models::{ExcerptSource, PublicRequestMeta, SliceResponse},
+    passphrase, revisions,
+    routes::{commits, relations, requests},
+    transcript::{
+        excerpt, parse_messages, slice_lines, slice_messages, MessageEntry, Range, RangeKind,
+    },
+    util::{count_lines, ContentKind},
+    AppState,
+};
 
 #[derive(Deserialize)]
-pub struct RevQuery {
+pub struct RawQuery {
     pub rev: Option<i32>,
+    pub lines: Option<String>,
+    pub messages: Option<String>,
+    pub format: Option<String>,
+    /// `strip` drops Markdown front matter; `keep` (the default) serves the body as stored.
+    pub front_matter: Option<String>,
 }
 
-#[derive(sqlx::FromRow)]
-struct ObjectRow {
-    object_key: String,
-    content_type: String,
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    pub lines: Option<bool>,
 }
 
 pub async fn front_page(
@@ -37,45 +65,314 @@ pub async fn front_page(
 pub async fn get_raw(
     State(state): State<AppState>,
     ClientIp(ip): ClientIp,
+    viewer: Viewer,
     Path(uuid): Path<Uuid>,
-    Query(q): Query<RevQuery>,
+    Query(q): Query<RawQuery>,
 ) -> Result<Response, ApiError> {
     state.public_read_limiter.check(&ip.to_string())?;
+    let grant = viewer.authorize_revision(&state, uuid).await?;
 
-    let row = if let Some(rev) = q.rev {
-        if rev < 1 {
-            return Err(ApiError::BadRequest("rev must be >= 1".to_string()));
+    let json = match q.format.as_deref() {
+        None | Some("raw") => false,
+        Some("json") => true,
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!("unsupported format: {other}")));
         }
-        sqlx::query_as::<_, ObjectRow>(
-            "SELECT object_key, content_type FROM request_revisions WHERE request_uuid = $1 AND rev_number = $2",
-        )
-        .bind(uuid)
-        .bind(rev)
-        .fetch_optional(&state.pool)
-        .await?
-    } else {
-        sqlx::query_as::<_, ObjectRow>(
-            "SELECT rr.object_key, rr.content_type \
-             FROM request_revisions rr \
-             JOIN requests r ON r.uuid = rr.request_uuid \
-             WHERE r.uuid = $1 AND rr.rev_number = r.latest_rev",
-        )
-        .bind(uuid)
-        .fetch_optional(&state.pool)
-        .await?
+    };
+    let strip_front_matter = match q.front_matter.as_deref() {
+        None | Some("keep") => false,
+        Some("strip") => true,
+        Some(other) => {
+            return Err(ApiError::BadRequest(format!(
+                "unsupported front_matter mode: {other}"
+            )));
+        }
+    };
+    let lines = q.lines.as_deref().map(Range::parse).transpose()?;
+    let messages = q.messages.as_deref().map(Range::parse).transpose()?;
+    if lines.is_some() && messages.is_some() {
+        return Err(ApiError::BadRequest(
+            "use either lines or messages, not both".to_string(),
+        ));
     }
-    .ok_or(ApiError::NotFound)?;
 
-    let bytes = state.store.get(&row.object_key).await?;
+    let revision = revisions::fetch(&state, uuid, q.rev).await?;
+    let kind = ContentKind::from_canonical(&revision.content_type);
+    if messages.is_some() && kind != Some(ContentKind::Jsonl) {
+        return Err(ApiError::BadRequest(
+            "messages ranges require a JSONL request".to_string(),
+        ));
+    }
 
-    let content_type = match row.content_type.as_str() {
+    let content_type = match revision.content_type.as_str() {
         "text/markdown" => ContentKind::Markdown.response_type(),
-        _ => row.content_type.as_str(),
+        _ => revision.content_type.as_str(),
     };
-
-    let mut resp = Response::new(Body::from(bytes));
-    let header = HeaderValue::from_str(content_type)
+    let content_type = HeaderValue::from_str(content_type)
         .unwrap_or_else(|_| HeaderValue::from_static("application/octet-stream"));
-    resp.headers_mut().insert(CONTENT_TYPE, header);
+
+    // Plain reads serve the stored bytes as they are, so they match the revision's sha256.
+    if !json && !strip_front_matter && lines.is_none() && messages.is_none() {
+        let total_lines = count_lines(&revision.bytes);
+        let mut resp = Response::new(Body::from(revision.bytes.clone()));
+        let headers = resp.headers_mut();
+        headers.insert(CONTENT_TYPE, content_type);
+        headers.insert("x-prompt-rev", HeaderValue::from(revision.rev));
+        headers.insert("x-total-lines", HeaderValue::from(total_lines));
+        if grant.count_view(&state).await? {
+            requests::burn(&state, uuid).await;
+        }
+        return Ok(resp);
+    }
+
+    let text = if strip_front_matter {
+        revision.text_without_front_matter()
+    } else {
+        revision.text()
+    };
+    let total_lines = text.lines().count();
+    let total_messages = (kind == Some(ContentKind::Jsonl)).then(|| parse_messages(&text).len());
+
+    let mut resp = if json {
+        let range = lines.unwrap_or(Range {
+            start: 1,
+            end: None,
+        });
+        let mut body = SliceResponse {
+            uuid,
+            rev: revision.rev,
+            content_type: revision.content_type.clone(),
+            total_lines,
+            total_messages,
+            start: 0,
+            count: 0,
+            lines: None,
+            messages: None,
+        };
+        if let Some(range) = messages {
+            let slice = slice_messages(&text, range);
+            body.start = slice.start;
+            body.count = slice.messages.len();
+            body.messages = Some(
+                slice
+                    .messages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(offset, m)| MessageEntry {
+                        index: slice.start + offset,
+                        line: m.line,
+                        message: m.value,
+                    })
+                    .collect(),
+            );
+        } else {
+            let slice = slice_lines(&text, range);
+            body.start = slice.start;
+            body.count = slice.lines.len();
+            body.lines = Some(slice.lines.into_iter().map(str::to_string).collect());
+        }
+        Json(body).into_response()
+    } else {
+        let body = if let Some(range) = lines {
+            excerpt(&text, RangeKind::Lines, range)
+        } else if let Some(range) = messages {
+            excerpt(&text, RangeKind::Messages, range)
+        } else {
+            text
+        };
+        let mut resp = Response::new(Body::from(body));
+        resp.headers_mut().insert(CONTENT_TYPE, content_type);
+        resp
+    };
+
+    let headers = resp.headers_mut();
+    headers.insert("x-prompt-rev", HeaderValue::from(revision.rev));
+    headers.insert("x-total-lines", HeaderValue::from(total_lines));
+    if let Some(total) = total_messages {
+        headers.insert("x-total-messages", HeaderValue::from(total));
+    }
+
+    // Counted only once the body is in hand, so a reader racing the burn still gets it.
+    if grant.count_view(&state).await? {
+        requests::burn(&state, uuid).await;
+    }
+    Ok(resp)
+}
+
+pub async fn get_diff(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    viewer: Viewer,
+    Path(uuid): Path<Uuid>,
+    Query(q): Query<DiffQuery>,
+) -> Result<Response, ApiError> {
+    state.public_read_limiter.check(&ip.to_string())?;
+    let format = q.validate()?;
+    viewer.authorize(&state, uuid).await?;
+
+    let from = revisions::fetch(&state, uuid, Some(q.from)).await?;
+    let to = revisions::fetch(&state, uuid, Some(q.to)).await?;
+
+    Ok(diff::render(uuid, &from, &to, format))
+}
+
+pub async fn get_events(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    viewer: Viewer,
+    Path(uuid): Path<Uuid>,
+    Query(q): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, ApiError> {
+    state.public_read_limiter.check(&ip.to_string())?;
+    let access = viewer.authorize_standing(&state, uuid).await?;
+
+    let guard = state.events.connect(ip)?;
+    let rx = state.events.subscribe();
+    let idle = state.events.idle_timeout;
+    let include_lines = q.lines.unwrap_or(false);
+
+    let stream = stream::unfold((rx, guard), move |(mut rx, guard)| {
+        let state = state.clone();
+        let viewer = viewer.clone();
+        let access = access.clone();
+        async move {
+            let deadline = Instant::now() + idle;
+            loop {
+                let event = match tokio::time::timeout_at(deadline, rx.recv()).await {
+                    Err(_) | Ok(Err(RecvError::Closed)) => return None,
+                    Ok(Err(RecvError::Lagged(_))) => continue,
+                    Ok(Ok(event)) if event.uuid != uuid => continue,
+                    Ok(Ok(event)) => event,
+                };
+                // Access may have changed since connecting: the request burned, hidden, or
+                // given a new passphrase. Such readers are disconnected.
+                if viewer.recheck(&state, uuid, &access).await.is_err() {
+                    return None;
+                }
+
+                let lines = if include_lines {
+                    appended_lines(&state, &event).await
+                } else {
+                    None
+                };
+                let sse = Event::default()
+                    .event("revision")
+                    .id(event.rev.to_string())
+                    .json_data(EventMessage { event, lines });
+                return Some((sse, (rx, guard)));
+            }
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// The messages a revision added on top of its predecessor, if it extends it.
+async fn appended_lines(state: &AppState, event: &RevisionEvent) -> Option<Vec<serde_json::Value>> {
+    if event.content_type != ContentKind::Jsonl.canonical_type() {
+        return None;
+    }
+    let previous = revisions::fetch(state, event.uuid, Some(event.previous_rev))
+        .await
+        .ok()?;
+    let current = revisions::fetch(state, event.uuid, Some(event.rev))
+        .await
+        .ok()?;
+    let added = current.bytes.strip_prefix(previous.bytes.as_ref())?;
+    let text = String::from_utf8_lossy(added);
+    Some(parse_messages(&text).into_iter().map(|m| m.value).collect())
+}
+
+pub async fn get_meta(
+    State(state): State<AppState>,
+    ClientIp(ip): ClientIp,
+    viewer: Viewer,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<PublicRequestMeta>, ApiError> {
+    state.public_read_limiter.check(&ip.to_string())?;
+    viewer.authorize(&state, uuid).await?;
+
+    #[derive(sqlx::FromRow)]
+    struct MetaRow {
+        latest_rev: i32,
+        content_type: String,
+        title: Option<String>,
+        summary: Option<String>,
+        created_at: DateTime<Utc>,
+        updated_at: DateTime<Utc>,
+    }
+
+    let row = sqlx::query_as::<_, MetaRow>(
+        "SELECT rr.rev_number AS latest_rev, rr.content_type, \
+                COALESCE(r.title_override, rr.title) AS title, \
+                COALESCE(r.summary_override, rr.summary) AS summary, \
+                r.created_at, r.updated_at \
+         FROM requests r \
+         JOIN LATERAL ( \
+             SELECT rev_number, content_type, title, summary \
+             FROM request_revisions \
+             WHERE request_uuid = r.uuid AND rev_number <= r.latest_rev \
+               AND (expires_at IS NULL OR expires_at > now()) \
+             ORDER BY rev_number DESC \
+             LIMIT 1 \
+         ) rr ON true \
+         WHERE r.uuid = $1",
+    )
+    .bind(uuid)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    let source = sqlx::query_as::<_, ExcerptSource>(
+        "SELECT source_uuid as uuid, source_rev as rev, source_sha256 as sha256, \
+                range_kind, range_start, range_end \
+         FROM request_sources WHERE request_uuid = $1",
+    )
+    .bind(uuid)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    let (parent, children) = relations::links(&state, uuid, viewer.reader_account()).await?;
+    let commits = commits::for_request(&state, uuid).await?;
+
+    Ok(Json(PublicRequestMeta {
+        uuid,
+        latest_rev: row.latest_rev,
+        content_type: row.content_type,
+        title: row.title,
+        summary: row.summary,
+        created_at: row.created_at,
+        updated_at: row.updated_at,
+        source,
+        parent,
+        children,
+        commits,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct UnlockRequest {
+    pub passphrase: String,
+}
+
+/// Verifies a passphrase and sets a cookie that unlocks the request for a day. Failed
+/// attempts are limited per IP by the passphrase limiter rather than the read limiter.
+pub async fn unlock(
+    State(state): State<AppState>,
+    viewer: Viewer,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<UnlockRequest>,
+) -> Result<Response, ApiError> {
+    let value = viewer.unlock(&state, uuid, &req.passphrase).await?;
+    let cookie = format!(
+        "{}={value}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax",
+        passphrase::cookie_name(uuid),
+        passphrase::UNLOCK_COOKIE_MAX_AGE_SECS,
+    );
+    let mut resp = StatusCode::NO_CONTENT.into_response();
+    resp.headers_mut().insert(
+        SET_COOKIE,
+        HeaderValue::from_str(&cookie).map_err(|e| ApiError::Internal(e.to_string()))?,
+    );
     Ok(resp)
 }
@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthContext,
+    error::ApiError,
+    models::{RelatedNode, RelationLink},
+    routes::requests::ensure_request_owner,
+    scopes::Scope,
+    AppState,
+};
+
+pub const PARENT_HEADER: &str = "x-prompt-parent";
+pub const RELATION_HEADER: &str = "x-prompt-relation";
+
+/// Relation trees deeper or larger than this are cut off when walked.
+const MAX_DEPTH: i32 = 64;
+const MAX_NODES: i64 = 1000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RelationKind {
+    SubagentOf,
+    Continues,
+    ForkedFrom,
+}
+
+impl RelationKind {
+    pub fn parse(raw: &str) -> Result<Self, ApiError> {
+        match raw.trim() {
+            "subagent_of" => Ok(RelationKind::SubagentOf),
+            "continues" => Ok(RelationKind::Continues),
+            "forked_from" => Ok(RelationKind::ForkedFrom),
+            other => Err(ApiError::BadRequest(format!(
+                "relation must be subagent_of, continues or forked_from, got {other}"
+            ))),
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RelationKind::SubagentOf => "subagent_of",
+            RelationKind::Continues => "continues",
+            RelationKind::ForkedFrom => "forked_from",
+        }
+    }
+}
+
+/// A link from a request to its parent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParentLink {
+    pub uuid: Uuid,
+    pub kind: RelationKind,
+}
+
+impl ParentLink {
+    /// Reads `X-Prompt-Parent: <uuid>` and `X-Prompt-Relation: <kind>`; both or neither must
+    /// be present.
+    pub fn from_headers(headers: &HeaderMap) -> Result<Option<Self>, ApiError> {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .map(|v| {
+                    v.to_str()
+                        .map_err(|_| ApiError::BadRequest(format!("invalid header {name}")))
+                })
+                .transpose()
+        };
+        match (header(PARENT_HEADER)?, header(RELATION_HEADER)?) {
+            (None, None) => Ok(None),
+            (Some(parent), Some(kind)) => Ok(Some(ParentLink {
+                uuid: parent.trim().parse().map_err(|_| {
+                    ApiError::BadRequest(format!("invalid {PARENT_HEADER}: {parent}"))
+                })?,
+                kind: RelationKind::parse(kind)?,
+            })),
+            _ => Err(ApiError::BadRequest(format!(
+                "{PARENT_HEADER} and {RELATION_HEADER} must be sent together"
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SetParentRequest {
+    pub parent: Uuid,
+    pub kind: String,
+}
+
+/// Links `child` to `link.uuid`. The parent must belong to `account_id` and must not be
+/// `child` itself or one of its descendants.
+pub async fn set_parent(
+    tx: &mut Transaction<'_, Postgres>,
+    account_id: i64,
+    child: Uuid,
+    link: ParentLink,
+) -> Result<(), ApiError> {
+    sqlx::query_scalar::<_, i32>(
+        "SELECT 1 FROM requests WHERE uuid = $1 AND account_id = $2 FOR UPDATE",
+    )
+    .bind(link.uuid)
+    .bind(account_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| ApiError::BadRequest("parent request not found".to_string()))?;
+
+    let creates_cycle: bool = sqlx::query_scalar(
+        "WITH RECURSIVE ancestors AS ( \
+             SELECT $1::uuid AS uuid, 0 AS depth \
+             UNION ALL \
+             SELECT rel.parent_uuid, a.depth + 1 \
+             FROM request_relations rel \
+             JOIN ancestors a ON rel.request_uuid = a.uuid \
+             WHERE a.depth < $3 \
+         ) \
+         SELECT EXISTS (SELECT 1 FROM ancestors WHERE uuid = $2)",
+    )
+    .bind(link.uuid)
+    .bind(child)
+    .bind(MAX_DEPTH)
+    .fetch_one(&mut **tx)
+    .await?;
+    if creates_cycle {
+        return Err(ApiError::BadRequest(
+            "a request cannot be linked below itself".to_string(),
+        ));
+    }
+
+    sqlx::query(
+        "INSERT INTO request_relations (request_uuid, parent_uuid, kind) VALUES ($1, $2, $3) \
+         ON CONFLICT (request_uuid) DO UPDATE \
+         SET parent_uuid = EXCLUDED.parent_uuid, kind = EXCLUDED.kind, created_at = now()",
+    )
+    .bind(child)
+    .bind(link.uuid)
+    .bind(link.kind.as_str())
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub async fn put_parent(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<SetParentRequest>,
+) -> Result<StatusCode, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    let link = ParentLink {
+        uuid: req.parent,
+        kind: RelationKind::parse(&req.kind)?,
+    };
+
+    let mut tx = state.pool.begin().await?;
+    sqlx::query_scalar::<_, i32>(
+        "SELECT 1 FROM requests WHERE uuid = $1 AND account_id = $2 FOR UPDATE",
+    )
+    .bind(uuid)
+    .bind(auth.account_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    set_parent(&mut tx, auth.account_id, uuid, link).await?;
+    tx.commit().await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn delete_parent(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<StatusCode, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    let res = sqlx::query("DELETE FROM request_relations WHERE request_uuid = $1")
+        .bind(uuid)
+        .execute(&state.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The whole tree the request belongs to, starting from its topmost ancestor.
+pub async fn get_related(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<RelatedNode>, ApiError> {
+    // The tree names other requests, so this needs read access to all of them.
+    auth.scopes.require_all(Scope::Read)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    let root: Uuid = sqlx::query_scalar(
+        "WITH RECURSIVE ancestors AS ( \
+             SELECT $1::uuid AS uuid, 0 AS depth \
+             UNION ALL \
+             SELECT rel.parent_uuid, a.depth + 1 \
+             FROM request_relations rel \
+             JOIN ancestors a ON rel.request_uuid = a.uuid \
+             WHERE a.depth < $2 \
+         ) \
+         SELECT uuid FROM ancestors ORDER BY depth DESC LIMIT 1",
+    )
+    .bind(uuid)
+    .bind(MAX_DEPTH)
+    .fetch_one(&state.pool)
+    .await?;
+
+    let rows = sqlx::query_as::<_, TreeRow>(
+        "WITH RECURSIVE tree AS ( \
+             SELECT $1::uuid AS uuid, NULL::uuid AS parent_uuid, NULL::text AS kind, 0 AS depth \
+             UNION ALL \
+             SELECT rel.request_uuid, rel.parent_uuid, rel.kind, t.depth + 1 \
+             FROM request_relations rel \
+             JOIN tree t ON rel.parent_uuid = t.uuid \
+             WHERE t.depth < $2 \
+         ) \
+         SELECT t.uuid, t.parent_uuid, t.kind, \
+                COALESCE(r.title_override, r.title) AS title, r.latest_rev, r.created_at \
+         FROM tree t \
+         JOIN requests r ON r.uuid = t.uuid \
+         ORDER BY t.depth, r.created_at \
+         LIMIT $3",
+    )
+    .bind(root)
+    .bind(MAX_DEPTH)
+    .bind(MAX_NODES)
+    .fetch_all(&state.pool)
+    .await?;
+
+    build_tree(root, rows)
+        .map(Json)
+        .ok_or_else(|| ApiError::Internal("relation tree has no root".to_string()))
+}
+
+/// Parent and children of a request, for navigation on the public view.
+pub async fn links(
+    state: &AppState,
+    uuid: Uuid,
+    viewer_account: Option<i64>,
+) -> Result<(Option<RelationLink>, Vec<RelationLink>), ApiError> {
+    // Only neighbours anyone may open are listed, unless the viewer owns them: public, with
+    // no passphrase and no view limit.
+    let parent = sqlx::query_as::<_, RelationLink>(
+        "SELECT rel.parent_uuid AS uuid, rel.kind, COALESCE(r.title_override, r.title) AS title \
+         FROM request_relations rel \
+         JOIN requests r ON r.uuid = rel.parent_uuid \
+         WHERE rel.request_uuid = $1 \
+           AND ((r.visibility = 'public' AND r.passphrase_hash IS NULL \
+                 AND r.views_remaining IS NULL) OR r.account_id = $2) \
+           AND (r.expires_at IS NULL OR r.expires_at > now())",
+    )
+    .bind(uuid)
+    .bind(viewer_account)
+    .fetch_optional(&state.pool)
+    .await?;
+
+    let children = sqlx::query_as::<_, RelationLink>(
+        "SELECT rel.request_uuid AS uuid, rel.kind, COALESCE(r.title_override, r.title) AS title \
+         FROM request_relations rel \
+         JOIN requests r ON r.uuid = rel.request_uuid \
+         WHERE rel.parent_uuid = $1 \
+           AND ((r.visibility = 'public' AND r.passphrase_hash IS NULL \
+                 AND r.views_remaining IS NULL) OR r.account_id = $3) \
+           AND (r.expires_at IS NULL OR r.expires_at > now()) \
+         ORDER BY r.created_at \
+         LIMIT $2",
+    )
+    .bind(uuid)
+    .bind(MAX_NODES)
+    .bind(viewer_account)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok((parent, children))
+}
+
+#[derive(sqlx::FromRow)]
+struct TreeRow {
+    uuid: Uuid,
+    parent_uuid: Option<Uuid>,
+    kind: Option<String>,
+    title: Option<String>,
+    latest_rev: i32,
+    created_at: DateTime<Utc>,
+}
+
+/// Nests flat rows under their parents. Rows must list parents before their children.
+fn build_tree(root: Uuid, rows: Vec<TreeRow>) -> Option<RelatedNode> {
+    let mut children: HashMap<Uuid, Vec<RelatedNode>> = HashMap::new();
+    let mut parents = Vec::with_capacity(rows.len());
+    let mut root_node = None;
+
+    for row in rows {
+        let node = RelatedNode {
+            uuid: row.uuid,
+            kind: row.kind,
+            title: row.title,
+            latest_rev: row.latest_rev,
+            created_at: row.created_at,
+            children: Vec::new(),
+        };
+        match row.parent_uuid {
+            Some(parent) => parents.push((parent, node)),
+            None if row.uuid == root => root_node = Some(node),
+            None => {}
+        }
+    }
+
+    // Attach deepest nodes first so every child is complete before it moves into its parent.
+    for (parent, node) in parents.into_iter().rev() {
+        let node = with_children(node, &mut children);
+        children.entry(parent).or_default().push(node);
+    }
+    root_node.map(|node| with_children(node, &mut children))
+}
+
+fn with_children(
+    mut node: RelatedNode,
+    children: &mut HashMap<Uuid, Vec<RelatedNode>>,
+) -> RelatedNode {
+    if let Some(mut kids) = children.remove(&node.uuid) {
+        kids.reverse();
+        node.children = kids;
+    }
+    node
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(uuid: Uuid, parent: Option<Uuid>, minute: u32) -> TreeRow {
+        TreeRow {
+            uuid,
+            parent_uuid: parent,
+            kind: parent.map(|_| "subagent_of".to_string()),
+            title: None,
+            latest_rev: 1,
+            created_at: DateTime::from_timestamp(i64::from(minute) * 60, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn nests_rows_in_order() {
+        let [root, a, b, a1] = [0u128, 1, 2, 3].map(Uuid::from_u128);
+        let rows = vec![
+            row(root, None, 0),
+            row(a, Some(root), 1),
+            row(b, Some(root), 2),
+            row(a1, Some(a), 3),
+        ];
+        let tree = build_tree(root, rows).unwrap();
+        assert_eq!(tree.uuid, root);
+        let kids: Vec<Uuid> = tree.children.iter().map(|n| n.uuid).collect();
+        assert_eq!(kids, vec![a, b]);
+        assert_eq!(tree.children[0].children[0].uuid, a1);
+        assert!(tree.children[1].children.is_empty());
+    }
+
+    #[test]
+    fn parses_relation_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(ParentLink::from_headers(&headers).unwrap(), None);
+
+        let parent = Uuid::from_u128(7);
+        headers.insert(PARENT_HEADER, parent.to_string().parse().unwrap());
+        assert!(ParentLink::from_headers(&headers).is_err());
+
+        headers.insert(RELATION_HEADER, "continues".parse().unwrap());
+        let link = ParentLink::from_headers(&headers).unwrap().unwrap();
+        assert_eq!(link.uuid, parent);
+        assert_eq!(link.kind, RelationKind::Continues);
+    }
+}
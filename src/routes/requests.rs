@@ -1,17 +1,41 @@
 use axum::{
     extract::{Path, Query, State},
     http::{HeaderMap, StatusCode},
+    response::Response,
     Json,
 };
+use axum_extra::extract::Query as MultiQuery;
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use serde_json::{Map, Value};
+use sqlx::{types::Json as SqlJson, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
+    access::{self, Visibility},
     auth::AuthContext,
+    blame,
+    diff::{self, DiffQuery},
     error::ApiError,
-    models::{RequestCreatedResponse, RequestListItem, RevisionInfo},
-    util::{object_key, parse_content_type, sha256_hex, MAX_UPLOAD_BYTES},
+    events::{self, RevisionEvent},
+    frontmatter,
+    models::{
+        BlameResponse, ExcerptCreatedResponse, ExcerptSource, RequestCreatedResponse,
+        RequestListItem, RevisionInfo, SearchResult,
+    },
+    passphrase, quota, retention, revisions,
+    routes::{
+        commits::{self, GitInfo},
+        relations::{self, ParentLink},
+        similar,
+        tags::{self, TagMode},
+    },
+    scopes::Scope,
+    simhash, summary,
+    transcript::{excerpt, search_text, Range, RangeKind},
+    upload,
+    util::{object_key, sha256_hex, ContentKind},
     AppState,
 };
 
@@ -19,6 +43,39 @@ use crate::{
 pub struct ListQuery {
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    /// JSON object the request metadata must contain, e.g. `{"model":"opus"}`.
+    pub meta: Option<String>,
+    #[serde(default)]
+    pub tag: Vec<String>,
+    pub tag_mode: Option<String>,
+}
+
+/// Title and summary overrides. A string sets the override, `null` clears it and an
+/// omitted field is left unchanged. `visibility`, `passphrase`, `max_views` and
+/// `expires_in_secs` (set or `null` to clear) change who can read the request, how often
+/// and for how long.
+#[derive(Deserialize)]
+pub struct PatchRequest {
+    #[serde(default, deserialize_with = "present")]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub summary: Option<Option<String>>,
+    pub visibility: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub passphrase: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub max_views: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub expires_in_secs: Option<Option<i64>>,
+}
+
+/// Distinguishes an explicit `null` (`Some(None)`) from an omitted field (`None`).
+pub(crate) fn present<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
 }
 
 #[derive(Deserialize)]
@@ -26,200 +83,928 @@ pub struct RevQuery {
     pub rev: Option<i32>,
 }
 
+#[derive(Deserialize)]
+pub struct AppendQuery {
+    pub expected_rev: i32,
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: String,
+    pub content_type: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateExcerptRequest {
+    pub rev: Option<i32>,
+    pub lines: Option<String>,
+    pub messages: Option<String>,
+    pub metadata: Option<Map<String, Value>>,
+    /// Defaults to the source request's visibility.
+    pub visibility: Option<String>,
+    /// Defaults to the account's default time-to-live.
+    pub expires_in_secs: Option<i64>,
+}
+
 pub async fn create_request(
     State(state): State<AppState>,
     auth: AuthContext,
     headers: HeaderMap,
     body: Bytes,
 ) -> Result<(StatusCode, Json<RequestCreatedResponse>), ApiError> {
-    if body.len() > MAX_UPLOAD_BYTES {
-        return Err(ApiError::PayloadTooLarge);
+    auth.scopes.require_scope(Scope::Create)?;
+    quota::check_upload_size(&auth.tier, body.len())?;
+
+    let upload = upload::parse(&headers, body).await?;
+    let parent = ParentLink::from_headers(&headers)?;
+    let ttl = retention::expires_in_from_headers(&headers, state.max_ttl_secs)?;
+    let check_similar = similar::hint_requested(&headers)?;
+    let protection = NewRequestProtection {
+        visibility: Visibility::from_headers(&headers)?.unwrap_or_default(),
+        passphrase_hash: passphrase_from_headers(&headers).await?,
+        max_views: access::max_views_from_headers(&headers)?,
+        expires_at: retention::new_request_expiry(&state, auth.account_id, ttl).await?,
+    };
+    let draft = RevisionDraft::new(upload.kind, &upload.body, upload.metadata, Map::new())?;
+    let git = GitInfo::from_upload(&headers, &draft.metadata)?;
+    let mut created = insert_new_request(
+        &state,
+        &auth,
+        upload.body,
+        draft,
+        protection,
+        NewRequestLinks {
+            source: None,
+            parent,
+            git,
+        },
+    )
+    .await?;
+    // The hint names other requests, which a key without full read access may not see.
+    if check_similar && auth.scopes.require_all(Scope::Read).is_ok() {
+        created.similar_to = similar::hint(&state, auth.account_id, created.uuid).await;
     }
 
-    let kind = parse_content_type(&headers)?;
-    let content_type = kind.canonical_type().to_string();
-    let sha256 = sha256_hex(&body);
-    let size_bytes = body.len() as i32;
+    Ok((StatusCode::CREATED, Json(created)))
+}
 
+pub async fn create_excerpt(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<CreateExcerptRequest>,
+) -> Result<(StatusCode, Json<ExcerptCreatedResponse>), ApiError> {
+    auth.scopes.require_scope(Scope::Create)?;
+    auth.scopes.require(Scope::Read, uuid)?;
+    let (range_kind, range) = match (req.lines.as_deref(), req.messages.as_deref()) {
+        (Some(lines), None) => (RangeKind::Lines, Range::parse(lines)?),
+        (None, Some(messages)) => (RangeKind::Messages, Range::parse(messages)?),
+        _ => {
+            return Err(ApiError::BadRequest(
+                "exactly one of lines or messages is required".to_string(),
+            ))
+        }
+    };
+    // Stored as INTEGER provenance; larger bounds could never select anything anyway.
+    let bound = |n: usize| {
+        i32::try_from(n).map_err(|_| ApiError::BadRequest("range is out of bounds".to_string()))
+    };
+    let range_start = bound(range.start)?;
+    let range_end = range.end.map(bound).transpose()?;
+    let ttl = req
+        .expires_in_secs
+        .map(|ttl| retention::validate_ttl(ttl, state.max_ttl_secs))
+        .transpose()?;
+    let (source_visibility, passphrase_hash): (String, Option<String>) = sqlx::query_as(
+        "SELECT visibility, passphrase_hash FROM requests \
+         WHERE uuid = $1 AND account_id = $2 \
+           AND (expires_at IS NULL OR expires_at > now())",
+    )
+    .bind(uuid)
+    .bind(auth.account_id)
+    .fetch_optional(&state.pool)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    let protection = NewRequestProtection {
+        visibility: Visibility::parse(req.visibility.as_deref().unwrap_or(&source_visibility))?,
+        passphrase_hash,
+        max_views: None,
+        expires_at: retention::new_request_expiry(&state, auth.account_id, ttl).await?,
+    };
+
+    let revision = revisions::fetch(&state, uuid, req.rev).await?;
+    let kind = ContentKind::from_canonical(&revision.content_type)
+        .ok_or_else(|| ApiError::Internal("unknown stored content type".to_string()))?;
+    if range_kind == RangeKind::Messages && kind != ContentKind::Jsonl {
+        return Err(ApiError::BadRequest(
+            "messages ranges require a JSONL request".to_string(),
+        ));
+    }
+
+    let text = excerpt(&revision.text(), range_kind, range);
+    if text.is_empty() {
+        return Err(ApiError::BadRequest("range selects nothing".to_string()));
+    }
+
+    let source = ExcerptSource {
+        uuid,
+        rev: revision.rev,
+        sha256: revision.sha256,
+        range_kind: range_kind.as_str().to_string(),
+        range_start,
+        range_end,
+    };
+    let draft = RevisionDraft::new(kind, text.as_bytes(), req.metadata, Map::new())?;
+    let created = insert_new_request(
+        &state,
+        &auth,
+        Bytes::from(text),
+        draft,
+        protection,
+        NewRequestLinks {
+            source: Some(&source),
+            parent: None,
+            git: GitInfo::default(),
+        },
+    )
+    .await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ExcerptCreatedResponse {
+            request: created,
+            source,
+        }),
+    ))
+}
+
+/// A revision about to be written: the fields derived from its body plus its metadata.
+struct RevisionDraft {
+    kind: ContentKind,
+    content_type: String,
+    size_bytes: i32,
+    sha256: String,
+    search_text: String,
+    title: Option<String>,
+    summary: Option<String>,
+    front_matter_bytes: i32,
+    simhash: Option<i64>,
+    metadata: Map<String, Value>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl RevisionDraft {
+    /// Metadata precedence is: explicit upload metadata, then Markdown front matter, then the
+    /// request's current metadata (`inherited`), which is only kept when no explicit metadata
+    /// was sent.
+    fn new(
+        kind: ContentKind,
+        body: &[u8],
+        metadata: Option<Map<String, Value>>,
+        inherited: Map<String, Value>,
+    ) -> Result<Self, ApiError> {
+        let text = String::from_utf8_lossy(body);
+        let front_matter = match kind {
+            ContentKind::Markdown => frontmatter::parse(&text)?,
+            ContentKind::Jsonl => None,
+        };
+
+        let mut merged = if metadata.is_some() {
+            Map::new()
+        } else {
+            inherited
+        };
+        let front_matter_bytes = match front_matter {
+            Some(front_matter) => {
+                merged.extend(front_matter.fields);
+                i32::try_from(front_matter.len).map_err(|_| ApiError::PayloadTooLarge)?
+            }
+            None => 0,
+        };
+        merged.extend(metadata.unwrap_or_default());
+        upload::validate_metadata(&merged)?;
+        let metadata = merged;
+
+        Ok(Self {
+            kind,
+            content_type: kind.canonical_type().to_string(),
+            size_bytes: i32::try_from(body.len()).map_err(|_| ApiError::PayloadTooLarge)?,
+            sha256: sha256_hex(body),
+            search_text: search_text(kind, &text),
+            title: summary::metadata_title(&metadata).or_else(|| summary::title(kind, &text)),
+            summary: summary::summary(kind, &text),
+            front_matter_bytes,
+            simhash: simhash::fingerprint(&text),
+            metadata,
+            expires_at: None,
+        })
+    }
+
+    /// Sets when this revision alone expires.
+    fn expiring(self, expires_at: Option<DateTime<Utc>>) -> Self {
+        Self { expires_at, ..self }
+    }
+
+    fn created(&self, uuid: Uuid, rev: i32, created_at: DateTime<Utc>) -> RequestCreatedResponse {
+        RequestCreatedResponse {
+            uuid,
+            rev,
+            content_type: self.content_type.clone(),
+            size_bytes: self.size_bytes,
+            sha256: self.sha256.clone(),
+            created_at,
+            similar_to: None,
+            visibility: None,
+            share_token: None,
+            expires_at: self.expires_at,
+        }
+    }
+
+    fn event(
+        &self,
+        uuid: Uuid,
+        rev: i32,
+        previous_rev: i32,
+        created_at: DateTime<Utc>,
+    ) -> RevisionEvent {
+        RevisionEvent {
+            uuid,
+            rev,
+            previous_rev,
+            content_type: self.content_type.clone(),
+            size_bytes: self.size_bytes,
+            sha256: self.sha256.clone(),
+            created_at,
+        }
+    }
+}
+
+/// `segment_bytes` holds the size of each object in `segment_keys`.
+async fn insert_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    uuid: Uuid,
+    rev: i32,
+    key: &str,
+    segment_keys: &[String],
+    segment_bytes: &[i64],
+    draft: &RevisionDraft,
+) -> Result<DateTime<Utc>, ApiError> {
+    let created_at = sqlx::query_scalar(
+        "INSERT INTO request_revisions \
+             (request_uuid, rev_number, content_type, size_bytes, sha256, object_key, segment_keys, \
+              segment_bytes, metadata, title, summary, front_matter_bytes, simhash, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14) \
+         RETURNING created_at",
+    )
+    .bind(uuid)
+    .bind(rev)
+    .bind(&draft.content_type)
+    .bind(draft.size_bytes)
+    .bind(&draft.sha256)
+    .bind(key)
+    .bind(segment_keys)
+    .bind(segment_bytes)
+    .bind(SqlJson(&draft.metadata))
+    .bind(&draft.title)
+    .bind(&draft.summary)
+    .bind(draft.front_matter_bytes)
+    .bind(draft.simhash)
+    .bind(draft.expires_at)
+    .fetch_one(&mut **tx)
+    .await?;
+    Ok(created_at)
+}
+
+/// Points the request at `rev` and copies the revision's derived fields onto it.
+async fn set_latest(
+    tx: &mut Transaction<'_, Postgres>,
+    uuid: Uuid,
+    rev: i32,
+    draft: &RevisionDraft,
+) -> Result<(), ApiError> {
+    sqlx::query(
+        "UPDATE requests SET latest_rev = $1, updated_at = now(), search_text = $2, metadata = $3, \
+             title = $4, summary = $5, simhash = $6 \
+         WHERE uuid = $7",
+    )
+    .bind(rev)
+    .bind(&draft.search_text)
+    .bind(SqlJson(&draft.metadata))
+    .bind(&draft.title)
+    .bind(&draft.summary)
+    .bind(draft.simhash)
+    .bind(uuid)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// A revision on top of `latest_rev` whose object is already stored under `key`.
+struct NewRevision<'a> {
+    uuid: Uuid,
+    account_id: i64,
+    latest_rev: i32,
+    key: &'a str,
+    segment_keys: &'a [String],
+    segment_bytes: &'a [i64],
+    draft: &'a RevisionDraft,
+    git: &'a GitInfo,
+    visibility: Option<Visibility>,
+}
+
+/// Writes the rows of a new revision and commits. Nothing is written if any step fails, and
+/// the caller then deletes the stored object, as [`insert_new_request`] does for rev 1.
+async fn commit_revision(
+    mut tx: Transaction<'_, Postgres>,
+    revision: NewRevision<'_>,
+) -> Result<RequestCreatedResponse, ApiError> {
+    let NewRevision {
+        uuid,
+        account_id,
+        latest_rev,
+        key,
+        segment_keys,
+        segment_bytes,
+        draft,
+        git,
+        visibility,
+    } = revision;
+    let rev = latest_rev + 1;
+
+    let rev_created_at =
+        insert_revision(&mut tx, uuid, rev, key, segment_keys, segment_bytes, draft).await?;
+    set_latest(&mut tx, uuid, rev, draft).await?;
+    commits::record(&mut tx, uuid, rev, git).await?;
+    let mut created = draft.created(uuid, rev, rev_created_at);
+    if let Some(visibility) = visibility {
+        created.share_token = access::set_visibility(&mut tx, uuid, account_id, visibility).await?;
+        created.visibility = Some(visibility.as_str().to_string());
+    }
+    events::notify(&mut tx, &draft.event(uuid, rev, latest_rev, rev_created_at)).await?;
+
+    tx.commit().await?;
+    Ok(created)
+}
+
+/// Rows a new request is linked to at creation.
+struct NewRequestLinks<'a> {
+    source: Option<&'a ExcerptSource>,
+    parent: Option<ParentLink>,
+    git: GitInfo,
+}
+
+/// Who may read a new request, how often and until when.
+struct NewRequestProtection {
+    visibility: Visibility,
+    passphrase_hash: Option<String>,
+    max_views: Option<i32>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Stores `body` as rev 1 of a fresh request. The object is written first and removed
+/// again if any database step fails, including the quota charge.
+async fn insert_new_request(
+    state: &AppState,
+    auth: &AuthContext,
+    body: Bytes,
+    draft: RevisionDraft,
+    protection: NewRequestProtection,
+    links: NewRequestLinks<'_>,
+) -> Result<RequestCreatedResponse, ApiError> {
     let uuid = Uuid::new_v4();
     let rev = 1;
-    let key = object_key(uuid, rev, kind);
+    let key = object_key(uuid, rev, draft.kind);
+    let visibility = protection.visibility;
+    let expires_at = protection.expires_at;
 
-    state.store.put(&key, body.clone(), &content_type).await?;
+    state.store.put(&key, body, &draft.content_type).await?;
+
+    match insert_new_request_rows(state, auth, uuid, &key, &draft, protection, links).await {
+        Ok((created_at, share_token)) => Ok(RequestCreatedResponse {
+            visibility: Some(visibility.as_str().to_string()),
+            share_token,
+            expires_at,
+            ..draft.created(uuid, rev, created_at)
+        }),
+        Err(err) => {
+            let _ = state.store.delete(&key).await;
+            Err(err)
+        }
+    }
+}
 
+async fn insert_new_request_rows(
+    state: &AppState,
+    auth: &AuthContext,
+    uuid: Uuid,
+    key: &str,
+    draft: &RevisionDraft,
+    protection: NewRequestProtection,
+    links: NewRequestLinks<'_>,
+) -> Result<(DateTime<Utc>, Option<String>), ApiError> {
+    let rev = 1;
+    let share_token = protection.visibility.initial_token();
     let mut tx = state.pool.begin().await?;
 
-    let create_res = sqlx::query(
-        "INSERT INTO requests (uuid, account_id, latest_rev) VALUES ($1, $2, $3)",
+    let size = i64::from(draft.size_bytes);
+    quota::charge(&mut tx, auth.account_id, &auth.tier, size, 1).await?;
+
+    sqlx::query(
+        "INSERT INTO requests \
+             (uuid, account_id, latest_rev, visibility, share_token, passphrase_hash, \
+              views_remaining, expires_at) \
+         VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
     )
     .bind(uuid)
     .bind(auth.account_id)
     .bind(rev)
+    .bind(protection.visibility.as_str())
+    .bind(&share_token)
+    .bind(protection.passphrase_hash)
+    .bind(protection.max_views)
+    .bind(protection.expires_at)
     .execute(&mut *tx)
-    .await;
+    .await?;
+
+    let rev_created_at =
+        insert_revision(&mut tx, uuid, rev, key, &[key.to_string()], &[size], draft).await?;
+    set_latest(&mut tx, uuid, rev, draft).await?;
+
+    if let Some(source) = links.source {
+        sqlx::query(
+            "INSERT INTO request_sources (request_uuid, source_uuid, source_rev, source_sha256, range_kind, range_start, range_end) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        )
+        .bind(uuid)
+        .bind(source.uuid)
+        .bind(source.rev)
+        .bind(&source.sha256)
+        .bind(&source.range_kind)
+        .bind(source.range_start)
+        .bind(source.range_end)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    if let Some(parent) = links.parent {
+        relations::set_parent(&mut tx, auth.account_id, uuid, parent).await?;
+    }
+    commits::record(&mut tx, uuid, rev, &links.git).await?;
+
+    tx.commit().await?;
+    Ok((rev_created_at, share_token))
+}
 
-    if let Err(err) = create_res {
-        let _ = state.store.delete(&key).await;
+pub async fn update_request(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<RequestCreatedResponse>), ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    quota::check_upload_size(&auth.tier, body.len())?;
+
+    let upload = upload::parse(&headers, body).await?;
+    let visibility = Visibility::from_headers(&headers)?;
+    let expires_at = retention::expires_at(retention::expires_in_from_headers(
+        &headers,
+        state.max_ttl_secs,
+    )?);
+
+    let mut tx = state.pool.begin().await?;
+
+    let (latest_rev, metadata) = lock_latest(&mut tx, uuid, auth.account_id).await?;
+
+    let draft = RevisionDraft::new(upload.kind, &upload.body, upload.metadata, metadata)?
+        .expiring(expires_at);
+    let git = GitInfo::from_upload(&headers, &draft.metadata)?;
+    let next_rev = latest_rev + 1;
+    let key = object_key(uuid, next_rev, upload.kind);
+    let size = i64::from(draft.size_bytes);
+    quota::charge(&mut tx, auth.account_id, &auth.tier, size, 0).await?;
+
+    if let Err(err) = state
+        .store
+        .put(&key, upload.body, &draft.content_type)
+        .await
+    {
         let _ = tx.rollback().await;
-        return Err(ApiError::from(err));
+        return Err(err);
     }
 
-    let rev_created_at = sqlx::query_scalar(
-        "INSERT INTO request_revisions (request_uuid, rev_number, content_type, size_bytes, sha256, object_key) \
-         VALUES ($1, $2, $3, $4, $5, $6) RETURNING created_at",
-    )
-    .bind(uuid)
-    .bind(rev)
-    .bind(&content_type)
-    .bind(size_bytes)
-    .bind(&sha256)
-    .bind(&key)
-    .fetch_one(&mut *tx)
-    .await;
-
-    let rev_created_at = match rev_created_at {
-        Ok(value) => value,
+    let revision = NewRevision {
+        uuid,
+        account_id: auth.account_id,
+        latest_rev,
+        key: &key,
+        segment_keys: std::slice::from_ref(&key),
+        segment_bytes: &[size],
+        draft: &draft,
+        git: &git,
+        visibility,
+    };
+    match commit_revision(tx, revision).await {
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
         Err(err) => {
             let _ = state.store.delete(&key).await;
-            let _ = tx.rollback().await;
-            return Err(ApiError::from(err));
+            Err(err)
+        }
+    }
+}
+
+pub async fn patch_request(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<PatchRequest>,
+) -> Result<Json<RequestListItem>, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    let title = override_value(req.title, "title", summary::MAX_TITLE_CHARS)?;
+    let summary = override_value(req.summary, "summary", summary::MAX_SUMMARY_CHARS)?;
+    let visibility = req
+        .visibility
+        .as_deref()
+        .map(Visibility::parse)
+        .transpose()?;
+    let passphrase_hash = match req.passphrase {
+        Some(Some(given)) => {
+            passphrase::validate(&given)?;
+            Some(Some(passphrase::hash(given).await?))
         }
+        Some(None) => Some(None),
+        None => None,
+    };
+    let max_views = req
+        .max_views
+        .map(|views| views.map(access::validate_max_views).transpose())
+        .transpose()?;
+    let expires_at = match req.expires_in_secs {
+        Some(Some(ttl)) => Some(retention::expires_at(Some(retention::validate_ttl(
+            ttl,
+            state.max_ttl_secs,
+        )?))),
+        Some(None) if state.max_ttl_secs.is_some() => {
+            return Err(ApiError::BadRequest(
+                "this instance requires requests to expire".to_string(),
+            ))
+        }
+        Some(None) => Some(None),
+        None => None,
     };
 
-    if let Err(err) = tx.commit().await {
-        let _ = state.store.delete(&key).await;
-        return Err(ApiError::from(err));
+    let mut tx = state.pool.begin().await?;
+    let res = sqlx::query(
+        "UPDATE requests SET \
+             title_override = CASE WHEN $3 THEN $4 ELSE title_override END, \
+             summary_override = CASE WHEN $5 THEN $6 ELSE summary_override END, \
+             passphrase_hash = CASE WHEN $7 THEN $8 ELSE passphrase_hash END, \
+             views_remaining = CASE WHEN $9 THEN $10 ELSE views_remaining END, \
+             expires_at = CASE WHEN $11 THEN $12 ELSE expires_at END \
+         WHERE uuid = $1 AND account_id = $2 \
+           AND (expires_at IS NULL OR expires_at > now())",
+    )
+    .bind(uuid)
+    .bind(auth.account_id)
+    .bind(title.is_some())
+    .bind(title.flatten())
+    .bind(summary.is_some())
+    .bind(summary.flatten())
+    .bind(passphrase_hash.is_some())
+    .bind(passphrase_hash.flatten())
+    .bind(max_views.is_some())
+    .bind(max_views.flatten())
+    .bind(expires_at.is_some())
+    .bind(expires_at.flatten())
+    .execute(&mut *tx)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    if let Some(visibility) = visibility {
+        access::set_visibility(&mut tx, uuid, auth.account_id, visibility).await?;
     }
+    tx.commit().await?;
 
-    Ok((
-        StatusCode::CREATED,
-        Json(RequestCreatedResponse {
-            uuid,
-            rev,
-            content_type,
-            size_bytes,
-            sha256,
-            created_at: rev_created_at,
-        }),
-    ))
+    let row = sqlx::query_as::<_, RequestListItem>(
+        "SELECT r.uuid, r.created_at, r.updated_at, r.latest_rev, \
+                rr.content_type as latest_content_type, r.metadata, \
+                COALESCE(r.title_override, r.title) AS title, \
+                COALESCE(r.summary_override, r.summary) AS summary, \
+                ARRAY(SELECT t.tag FROM request_tags t WHERE t.request_uuid = r.uuid ORDER BY t.tag) AS tags, \
+                r.visibility, r.share_token, \
+                r.passphrase_hash IS NOT NULL AS passphrase_protected, r.views_remaining, \
+                r.expires_at \
+         FROM requests r \
+         JOIN request_revisions rr \
+           ON rr.request_uuid = r.uuid AND rr.rev_number = r.latest_rev \
+         WHERE r.uuid = $1",
+    )
+    .bind(uuid)
+    .fetch_one(&state.pool)
+    .await?;
+
+    Ok(Json(row))
 }
 
-pub async fn update_request(
+/// Hashes the passphrase from `X-Prompt-Passphrase`, if one was sent.
+async fn passphrase_from_headers(headers: &HeaderMap) -> Result<Option<String>, ApiError> {
+    let Some(value) = headers.get(passphrase::PASSPHRASE_HEADER) else {
+        return Ok(None);
+    };
+    let given = value
+        .to_str()
+        .map_err(|_| ApiError::BadRequest("invalid passphrase header".to_string()))?;
+    passphrase::validate(given)?;
+    Ok(Some(passphrase::hash(given.to_string()).await?))
+}
+
+/// Trims an override and enforces its length; an empty string clears it like `null`.
+fn override_value(
+    value: Option<Option<String>>,
+    field: &str,
+    max_chars: usize,
+) -> Result<Option<Option<String>>, ApiError> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let value = value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    if value
+        .as_ref()
+        .is_some_and(|v| v.chars().count() > max_chars)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "{field} must be at most {max_chars} characters"
+        )));
+    }
+    Ok(Some(value))
+}
+
+pub async fn append_request(
     State(state): State<AppState>,
     auth: AuthContext,
     Path(uuid): Path<Uuid>,
+    Query(q): Query<AppendQuery>,
     headers: HeaderMap,
     body: Bytes,
 ) -> Result<(StatusCode, Json<RequestCreatedResponse>), ApiError> {
-    if body.len() > MAX_UPLOAD_BYTES {
-        return Err(ApiError::PayloadTooLarge);
-    }
+    auth.scopes.require(Scope::Update, uuid)?;
+    quota::check_upload_size(&auth.tier, body.len())?;
 
-    let kind = parse_content_type(&headers)?;
-    let content_type = kind.canonical_type().to_string();
-    let sha256 = sha256_hex(&body);
-    let size_bytes = body.len() as i32;
+    let upload = upload::parse(&headers, body).await?;
+    if upload.kind != ContentKind::Jsonl {
+        return Err(ApiError::BadRequest(
+            "append requires application/x-ndjson".to_string(),
+        ));
+    }
+    validate_jsonl_lines(&upload.body)?;
+    let expires_at = retention::expires_at(retention::expires_in_from_headers(
+        &headers,
+        state.max_ttl_secs,
+    )?);
 
     let mut tx = state.pool.begin().await?;
 
-    let latest_rev: i32 = sqlx::query_scalar(
-        "SELECT latest_rev FROM requests WHERE uuid = $1 AND account_id = $2 FOR UPDATE",
-    )
-    .bind(uuid)
-    .bind(auth.account_id)
-    .fetch_optional(&mut *tx)
-    .await?
-    .ok_or(ApiError::NotFound)?;
+    let (latest_rev, metadata) = lock_latest(&mut tx, uuid, auth.account_id).await?;
+
+    if latest_rev != q.expected_rev {
+        return Err(ApiError::Conflict(format!(
+            "expected rev {} but latest is {latest_rev}",
+            q.expected_rev
+        )));
+    }
+
+    let latest = revisions::fetch(&state, uuid, Some(latest_rev)).await?;
+    if latest.content_type != ContentKind::Jsonl.canonical_type() {
+        return Err(ApiError::BadRequest(
+            "append is only supported for JSONL requests".to_string(),
+        ));
+    }
+
+    let mut chunk = Vec::with_capacity(upload.body.len() + 1);
+    if !latest.bytes.is_empty() && !latest.bytes.ends_with(b"\n") {
+        chunk.push(b'\n');
+    }
+    chunk.extend_from_slice(&upload.body);
+
+    let mut full = Vec::with_capacity(latest.bytes.len() + chunk.len());
+    full.extend_from_slice(&latest.bytes);
+    full.extend_from_slice(&chunk);
+
+    let draft = RevisionDraft::new(ContentKind::Jsonl, &full, upload.metadata, metadata)?
+        .expiring(expires_at);
+    let git = GitInfo::from_upload(&headers, &draft.metadata)?;
 
     let next_rev = latest_rev + 1;
-    let key = object_key(uuid, next_rev, kind);
+    let key = object_key(uuid, next_rev, ContentKind::Jsonl);
+
+    let mut segment_keys = latest.segment_keys;
+    let mut segment_bytes = latest.segment_bytes;
+    let object = if segment_keys.len() + 1 > revisions::MAX_SEGMENTS {
+        segment_keys.clear();
+        segment_bytes.clear();
+        Bytes::from(full)
+    } else {
+        Bytes::from(chunk)
+    };
+    segment_keys.push(key.clone());
+    segment_bytes.push(object.len() as i64);
+    // Only the new object is charged; compaction's old segments are released once the
+    // revisions that share them are deleted.
+    quota::charge(&mut tx, auth.account_id, &auth.tier, object.len() as i64, 0).await?;
 
-    if let Err(err) = state.store.put(&key, body.clone(), &content_type).await {
+    if let Err(err) = state.store.put(&key, object, &draft.content_type).await {
         let _ = tx.rollback().await;
         return Err(err);
     }
 
-    let rev_created_at = sqlx::query_scalar(
-        "INSERT INTO request_revisions (request_uuid, rev_number, content_type, size_bytes, sha256, object_key) \
-         VALUES ($1, $2, $3, $4, $5, $6) RETURNING created_at",
-    )
-    .bind(uuid)
-    .bind(next_rev)
-    .bind(&content_type)
-    .bind(size_bytes)
-    .bind(&sha256)
-    .bind(&key)
-    .fetch_one(&mut *tx)
-    .await;
-
-    let rev_created_at = match rev_created_at {
-        Ok(value) => value,
+    let revision = NewRevision {
+        uuid,
+        account_id: auth.account_id,
+        latest_rev,
+        key: &key,
+        segment_keys: &segment_keys,
+        segment_bytes: &segment_bytes,
+        draft: &draft,
+        git: &git,
+        visibility: None,
+    };
+    match commit_revision(tx, revision).await {
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
         Err(err) => {
             let _ = state.store.delete(&key).await;
-            let _ = tx.rollback().await;
-            return Err(ApiError::from(err));
+            Err(err)
         }
-    };
-
-    sqlx::query("UPDATE requests SET latest_rev = $1, updated_at = now() WHERE uuid = $2")
-        .bind(next_rev)
-        .bind(uuid)
-        .execute(&mut *tx)
-        .await?;
-
-    tx.commit().await?;
+    }
+}
 
-    Ok((
-        StatusCode::CREATED,
-        Json(RequestCreatedResponse {
-            uuid,
-            rev: next_rev,
-            content_type,
-            size_bytes,
-            sha256,
-            created_at: rev_created_at,
-        }),
-    ))
+/// Locks the account's request row for a new revision, returning its latest rev and metadata.
+async fn lock_latest(
+    tx: &mut Transaction<'_, Postgres>,
+    uuid: Uuid,
+    account_id: i64,
+) -> Result<(i32, Map<String, Value>), ApiError> {
+    let (latest_rev, metadata): (i32, SqlJson<Map<String, Value>>) = sqlx::query_as(
+        "SELECT latest_rev, metadata FROM requests \
+         WHERE uuid = $1 AND account_id = $2 AND (expires_at IS NULL OR expires_at > now()) \
+         FOR UPDATE",
+    )
+    .bind(uuid)
+    .bind(account_id)
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+    Ok((latest_rev, metadata.0))
 }
 
 pub async fn list_requests(
     State(state): State<AppState>,
     auth: AuthContext,
-    Query(q): Query<ListQuery>,
+    MultiQuery(q): MultiQuery<ListQuery>,
 ) -> Result<Json<Vec<RequestListItem>>, ApiError> {
+    auth.scopes.require_all(Scope::Read)?;
     let limit = q.limit.unwrap_or(50).clamp(1, 100);
     let offset = q.offset.unwrap_or(0).max(0);
+    let meta = q.meta.as_deref().map(parse_meta_filter).transpose()?;
+    let tags = tags::normalize_all(&q.tag)?;
+    let required_tags: i64 = match TagMode::parse(q.tag_mode.as_deref())? {
+        TagMode::All => tags.len() as i64,
+        TagMode::Any => 1,
+    };
 
+    // Describe each request by its newest unexpired revision, as reads of the latest do.
     let rows = sqlx::query_as::<_, RequestListItem>(
-        "SELECT r.uuid, r.created_at, r.updated_at, r.latest_rev, \
-                rr.content_type as latest_content_type \
+        "SELECT r.uuid, r.created_at, r.updated_at, rr.rev_number AS latest_rev, \
+                rr.content_type as latest_content_type, rr.metadata, \
+                COALESCE(r.title_override, rr.title) AS title, \
+                COALESCE(r.summary_override, rr.summary) AS summary, \
+                ARRAY(SELECT t.tag FROM request_tags t WHERE t.request_uuid = r.uuid ORDER BY t.tag) AS tags, \
+                r.visibility, r.share_token, \
+                r.passphrase_hash IS NOT NULL AS passphrase_protected, r.views_remaining, \
+                r.expires_at \
          FROM requests r \
-         JOIN request_revisions rr \
-           ON rr.request_uuid = r.uuid AND rr.rev_number = r.latest_rev \
-         WHERE r.account_id = $1 \
+         JOIN LATERAL ( \
+             SELECT rev_number, content_type, metadata, title, summary \
+             FROM request_revisions \
+             WHERE request_uuid = r.uuid AND rev_number <= r.latest_rev \
+               AND (expires_at IS NULL OR expires_at > now()) \
+             ORDER BY rev_number DESC \
+             LIMIT 1 \
+         ) rr ON true \
+         WHERE r.account_id = $1 AND ($4::jsonb IS NULL OR rr.metadata @> $4) \
+           AND (r.expires_at IS NULL OR r.expires_at > now()) \
+           AND (cardinality($5::text[]) = 0 OR ( \
+               SELECT COUNT(*) FROM request_tags t \
+               WHERE t.request_uuid = r.uuid AND t.tag = ANY($5) \
+           ) >= $6) \
          ORDER BY r.created_at DESC \
          LIMIT $2 OFFSET $3",
     )
     .bind(auth.account_id)
     .bind(limit)
     .bind(offset)
+    .bind(meta.map(SqlJson))
+    .bind(&tags)
+    .bind(required_tags)
     .fetch_all(&state.pool)
     .await?;
 
     Ok(Json(rows))
 }
 
+pub async fn search_requests(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Query(q): Query<SearchQuery>,
+) -> Result<Json<Vec<SearchResult>>, ApiError> {
+    auth.scopes.require_all(Scope::Read)?;
+    if q.q.trim().is_empty() {
+        return Err(ApiError::BadRequest("q is required".to_string()));
+    }
+    let content_type = q
+        .content_type
+        .as_deref()
+        .map(|raw| {
+            ContentKind::from_mime(&raw.trim().to_ascii_lowercase())
+                .map(|kind| kind.canonical_type())
+                .ok_or_else(|| ApiError::BadRequest(format!("unsupported content_type: {raw}")))
+        })
+        .transpose()?;
+    let limit = q.limit.unwrap_or(20).clamp(1, 100);
+    let offset = q.offset.unwrap_or(0).max(0);
+
+    // Rank and page first so ts_headline only runs on the rows that are returned. The index
+    // holds the latest revision's text, so requests whose latest revision has expired are
+    // left out until the sweeper deletes it and reindexes the one before.
+    let rows = sqlx::query_as::<_, SearchResult>(
+        "WITH matches AS ( \
+             SELECT r.uuid, ts_rank(r.search_vector, query) AS rank, query \
+             FROM requests r \
+             JOIN request_revisions rr \
+               ON rr.request_uuid = r.uuid AND rr.rev_number = r.latest_rev, \
+                  websearch_to_tsquery('english', $2) query \
+             WHERE r.account_id = $1 \
+               AND r.search_vector @@ query \
+               AND (r.expires_at IS NULL OR r.expires_at > now()) \
+               AND (rr.expires_at IS NULL OR rr.expires_at > now()) \
+               AND ($3::text IS NULL OR rr.content_type = $3) \
+             ORDER BY rank DESC, r.created_at DESC \
+             LIMIT $4 OFFSET $5 \
+         ) \
+         SELECT r.uuid, r.created_at, r.updated_at, r.latest_rev, \
+                rr.content_type as latest_content_type, r.metadata, \
+                COALESCE(r.title_override, r.title) AS title, \
+                COALESCE(r.summary_override, r.summary) AS summary, \
+                ARRAY(SELECT t.tag FROM request_tags t WHERE t.request_uuid = r.uuid ORDER BY t.tag) AS tags, \
+                r.visibility, r.share_token, \
+                r.passphrase_hash IS NOT NULL AS passphrase_protected, r.views_remaining, \
+                r.expires_at, \
+                m.rank, \
+                ts_headline('english', r.search_text, m.query, \
+                    'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=30, MinWords=10') AS snippet \
+         FROM matches m \
+         JOIN requests r ON r.uuid = m.uuid \
+         JOIN request_revisions rr \
+           ON rr.request_uuid = r.uuid AND rr.rev_number = r.latest_rev \
+         ORDER BY m.rank DESC, r.created_at DESC",
+    )
+    .bind(auth.account_id)
+    .bind(q.q.trim())
+    .bind(content_type)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+fn parse_meta_filter(raw: &str) -> Result<Value, ApiError> {
+    match serde_json::from_str::<Value>(raw) {
+        Ok(value @ Value::Object(_)) => Ok(value),
+        _ => Err(ApiError::BadRequest(
+            "meta must be a JSON object".to_string(),
+        )),
+    }
+}
+
 pub async fn list_revisions(
     State(state): State<AppState>,
     auth: AuthContext,
     Path(uuid): Path<Uuid>,
 ) -> Result<Json<Vec<RevisionInfo>>, ApiError> {
+    auth.scopes.require(Scope::Read, uuid)?;
     ensure_request_owner(&state, uuid, auth.account_id).await?;
 
     let rows = sqlx::query_as::<_, RevisionInfo>(
-        "SELECT rev_number as rev, created_at, content_type, size_bytes, sha256 \
+        "SELECT rev_number as rev, created_at, content_type, size_bytes, sha256, metadata, \
+                title, summary, expires_at \
          FROM request_revisions \
-         WHERE request_uuid = $1 \
+         WHERE request_uuid = $1 AND (expires_at IS NULL OR expires_at > now()) \
          ORDER BY rev_number DESC",
     )
     .bind(uuid)
@@ -234,15 +1019,18 @@ pub async fn get_revision_metadata(
     auth: AuthContext,
     Path((uuid, rev)): Path<(Uuid, i32)>,
 ) -> Result<Json<RevisionInfo>, ApiError> {
+    auth.scopes.require(Scope::Read, uuid)?;
     if rev < 1 {
         return Err(ApiError::BadRequest("rev must be >= 1".to_string()));
     }
     ensure_request_owner(&state, uuid, auth.account_id).await?;
 
     let row = sqlx::query_as::<_, RevisionInfo>(
-        "SELECT rev_number as rev, created_at, content_type, size_bytes, sha256 \
+        "SELECT rev_number as rev, created_at, content_type, size_bytes, sha256, metadata, \
+                title, summary, expires_at \
          FROM request_revisions \
-         WHERE request_uuid = $1 AND rev_number = $2",
+         WHERE request_uuid = $1 AND rev_number = $2 \
+           AND (expires_at IS NULL OR expires_at > now())",
     )
     .bind(uuid)
     .bind(rev)
@@ -253,12 +1041,40 @@ pub async fn get_revision_metadata(
     Ok(Json(row))
 }
 
+pub async fn diff_revisions(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Query(q): Query<DiffQuery>,
+) -> Result<Response, ApiError> {
+    auth.scopes.require(Scope::Read, uuid)?;
+    let format = q.validate()?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    let from = revisions::fetch(&state, uuid, Some(q.from)).await?;
+    let to = revisions::fetch(&state, uuid, Some(q.to)).await?;
+
+    Ok(diff::render(uuid, &from, &to, format))
+}
+
+pub async fn blame_revision(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Query(q): Query<RevQuery>,
+) -> Result<Json<BlameResponse>, ApiError> {
+    auth.scopes.require(Scope::Read, uuid)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+    Ok(Json(blame::compute(&state, uuid, q.rev).await?))
+}
+
 pub async fn delete_request(
     State(state): State<AppState>,
     auth: AuthContext,
     Path(uuid): Path<Uuid>,
     Query(q): Query<RevQuery>,
 ) -> Result<StatusCode, ApiError> {
+    auth.scopes.require(Scope::Delete, uuid)?;
     if let Some(rev) = q.rev {
         delete_revision(&state, uuid, auth.account_id, rev).await?;
     } else {
@@ -267,7 +1083,7 @@ pub async fn delete_request(
     Ok(StatusCode::NO_CONTENT)
 }
 
-async fn delete_revision(
+pub(crate) async fn delete_revision(
     state: &AppState,
     uuid: Uuid,
     account_id: i64,
@@ -289,13 +1105,9 @@ async fn delete_revision(
         return Err(ApiError::NotFound);
     }
 
-    #[derive(sqlx::FromRow)]
-    struct ObjectRow {
-        object_key: String,
-    }
-
-    let row = sqlx::query_as::<_, ObjectRow>(
-        "SELECT object_key FROM request_revisions WHERE request_uuid = $1 AND rev_number = $2",
+    let (segment_keys, segment_bytes): (Vec<String>, Vec<i64>) = sqlx::query_as(
+        "SELECT segment_keys, segment_bytes FROM request_revisions \
+         WHERE request_uuid = $1 AND rev_number = $2",
     )
     .bind(uuid)
     .bind(rev)
@@ -303,44 +1115,158 @@ async fn delete_revision(
     .await?
     .ok_or(ApiError::NotFound)?;
 
-    sqlx::query(
-        "DELETE FROM request_revisions WHERE request_uuid = $1 AND rev_number = $2",
-    )
-    .bind(uuid)
-    .bind(rev)
-    .execute(&mut *tx)
-    .await?;
+    sqlx::query("DELETE FROM request_revisions WHERE request_uuid = $1 AND rev_number = $2")
+        .bind(uuid)
+        .bind(rev)
+        .execute(&mut *tx)
+        .await?;
 
-    let max_rev: Option<i32> = sqlx::query_scalar(
-        "SELECT MAX(rev_number) FROM request_revisions WHERE request_uuid = $1",
-    )
-    .bind(uuid)
-    .fetch_one(&mut *tx)
-    .await?;
+    // Later revisions were blamed through the deleted one.
+    sqlx::query("DELETE FROM revision_blame WHERE request_uuid = $1 AND rev_number > $2")
+        .bind(uuid)
+        .bind(rev)
+        .execute(&mut *tx)
+        .await?;
 
-    if let Some(max_rev) = max_rev {
-        sqlx::query("UPDATE requests SET latest_rev = $1, updated_at = now() WHERE uuid = $2")
-            .bind(max_rev)
+    let keys = revisions::unreferenced_keys(&mut tx, uuid, &segment_keys).await?;
+    let freed: i64 = segment_keys
+        .iter()
+        .zip(&segment_bytes)
+        .filter(|(key, _)| keys.contains(key))
+        .map(|(_, bytes)| bytes)
+        .sum();
+
+    let max_rev: Option<i32> =
+        sqlx::query_scalar("SELECT MAX(rev_number) FROM request_revisions WHERE request_uuid = $1")
             .bind(uuid)
-            .execute(&mut *tx)
+            .fetch_one(&mut *tx)
             .await?;
+
+    if let Some(max_rev) = max_rev {
+        sqlx::query(
+            "UPDATE requests r SET latest_rev = $1, updated_at = now(), \
+                 metadata = rr.metadata, title = rr.title, summary = rr.summary, \
+                 simhash = rr.simhash \
+             FROM request_revisions rr \
+             WHERE r.uuid = $2 AND rr.request_uuid = $2 AND rr.rev_number = $1",
+        )
+        .bind(max_rev)
+        .bind(uuid)
+        .execute(&mut *tx)
+        .await?;
     } else {
         sqlx::query("DELETE FROM requests WHERE uuid = $1")
             .bind(uuid)
             .execute(&mut *tx)
             .await?;
     }
+    quota::release(&mut tx, account_id, freed, i64::from(max_rev.is_none())).await?;
 
     tx.commit().await?;
 
-    if let Err(err) = state.store.delete(&row.object_key).await {
-        tracing::warn!("failed to delete object {}: {}", row.object_key, err);
+    if max_rev.is_some_and(|max_rev| rev > max_rev) {
+        refresh_search_text(state, uuid).await;
+    }
+
+    for key in keys {
+        if let Err(err) = state.store.delete(&key).await {
+            tracing::warn!("failed to delete object {}: {}", key, err);
+        }
     }
 
     Ok(())
 }
 
-async fn delete_all(state: &AppState, uuid: Uuid, account_id: i64) -> Result<(), ApiError> {
+/// Re-indexes the latest revision, e.g. after the previous latest was deleted. Best effort: a
+/// failure leaves the old text indexed until the next upload.
+async fn refresh_search_text(state: &AppState, uuid: Uuid) {
+    let latest = match revisions::fetch(state, uuid, None).await {
+        Ok(latest) => latest,
+        Err(err) => {
+            tracing::warn!("failed to reindex request {}: {}", uuid, err);
+            return;
+        }
+    };
+    let Some(kind) = ContentKind::from_canonical(&latest.content_type) else {
+        return;
+    };
+
+    let res =
+        sqlx::query("UPDATE requests SET search_text = $1 WHERE uuid = $2 AND latest_rev = $3")
+            .bind(search_text(kind, &latest.text()))
+            .bind(uuid)
+            .bind(latest.rev)
+            .execute(&state.pool)
+            .await;
+    if let Err(err) = res {
+        tracing::warn!("failed to reindex request {}: {}", uuid, err);
+    }
+}
+
+/// Requests indexed per batch by [`backfill_search_text`].
+const BACKFILL_BATCH: i64 = 100;
+
+/// Indexes requests that have never been indexed, e.g. ones created before search existed,
+/// in the background.
+pub fn spawn_search_backfill(state: AppState) {
+    tokio::spawn(async move {
+        match backfill_search_text(&state).await {
+            Ok(0) => {}
+            Ok(visited) => tracing::info!(visited, "backfilled search text"),
+            Err(err) => tracing::warn!(error = %err, "search backfill failed"),
+        }
+    });
+}
+
+/// Runs one pass over the requests without search text and returns how many it visited.
+/// Each is visited once; ones that fail to index stay unindexed until the next pass.
+pub async fn backfill_search_text(state: &AppState) -> Result<usize, ApiError> {
+    let mut visited = 0;
+    let mut after = Uuid::nil();
+    loop {
+        let batch: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT uuid FROM requests WHERE search_text IS NULL AND uuid > $1 \
+             ORDER BY uuid LIMIT $2",
+        )
+        .bind(after)
+        .bind(BACKFILL_BATCH)
+        .fetch_all(&state.pool)
+        .await?;
+        for uuid in &batch {
+            refresh_search_text(state, *uuid).await;
+        }
+        visited += batch.len();
+        match batch.last() {
+            Some(last) if batch.len() as i64 == BACKFILL_BATCH => after = *last,
+            _ => return Ok(visited),
+        }
+    }
+}
+
+/// Deletes a request after its last allowed view. Its `burned_requests` tombstone was
+/// written with the final count, so later reads answer `410` either way; a failure here
+/// only leaves the objects behind.
+pub(crate) async fn burn(state: &AppState, uuid: Uuid) {
+    let owner: Result<Option<i64>, sqlx::Error> =
+        sqlx::query_scalar("SELECT account_id FROM requests WHERE uuid = $1")
+            .bind(uuid)
+            .fetch_optional(&state.pool)
+            .await;
+    let res = match owner {
+        Ok(Some(owner)) => delete_all(state, uuid, owner).await,
+        Ok(None) => return,
+        Err(err) => Err(err.into()),
+    };
+    if let Err(err) = res {
+        tracing::warn!("failed to burn request {}: {}", uuid, err);
+    }
+}
+
+pub(crate) async fn delete_all(
+    state: &AppState,
+    uuid: Uuid,
+    account_id: i64,
+) -> Result<(), ApiError> {
     let mut tx = state.pool.begin().await?;
 
     let owner: i64 = sqlx::query_scalar("SELECT account_id FROM requests WHERE uuid = $1")
@@ -353,12 +1279,16 @@ async fn delete_all(state: &AppState, uuid: Uuid, account_id: i64) -> Result<(),
         return Err(ApiError::NotFound);
     }
 
-    let keys: Vec<String> = sqlx::query_scalar(
-        "SELECT object_key FROM request_revisions WHERE request_uuid = $1",
+    let objects: Vec<(String, i64)> = sqlx::query_as(
+        "SELECT DISTINCT ON (s.key) s.key, s.bytes \
+         FROM request_revisions rr, unnest(rr.segment_keys, rr.segment_bytes) AS s(key, bytes) \
+         WHERE rr.request_uuid = $1",
     )
     .bind(uuid)
     .fetch_all(&mut *tx)
     .await?;
+    let freed = objects.iter().map(|(_, bytes)| bytes).sum();
+    let keys: Vec<String> = objects.into_iter().map(|(key, _)| key).collect();
 
     sqlx::query("DELETE FROM request_revisions WHERE request_uuid = $1")
         .bind(uuid)
@@ -368,6 +1298,7 @@ async fn delete_all(state: &AppState, uuid: Uuid, account_id: i64) -> Result<(),
         .bind(uuid)
         .execute(&mut *tx)
         .await?;
+    quota::release(&mut tx, account_id, freed, 1).await?;
 
     tx.commit().await?;
 
@@ -380,13 +1311,33 @@ async fn delete_all(state: &AppState, uuid: Uuid, account_id: i64) -> Result<(),
     Ok(())
 }
 
-async fn ensure_request_owner(
+fn validate_jsonl_lines(body: &[u8]) -> Result<(), ApiError> {
+    let text = std::str::from_utf8(body)
+        .map_err(|_| ApiError::BadRequest("body must be utf-8".to_string()))?;
+    let mut count = 0;
+    for (idx, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        serde_json::from_str::<serde_json::Value>(line).map_err(|e| {
+            ApiError::BadRequest(format!("line {} is not valid JSON: {e}", idx + 1))
+        })?;
+        count += 1;
+    }
+    if count == 0 {
+        return Err(ApiError::BadRequest("no lines to append".to_string()));
+    }
+    Ok(())
+}
+
+pub(crate) async fn ensure_request_owner(
     state: &AppState,
     uuid: Uuid,
     account_id: i64,
 ) -> Result<(), ApiError> {
-    let exists = sqlx::query_scalar::<_, i64>(
-        "SELECT 1 FROM requests WHERE uuid = $1 AND account_id = $2",
+    let exists = sqlx::query_scalar::<_, i32>(
+        "SELECT 1 FROM requests \
+         WHERE uuid = $1 AND account_id = $2 AND (expires_at IS NULL OR expires_at > now())",
     )
     .bind(uuid)
     .bind(account_id)
@@ -0,0 +1,171 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    access::{validate_max_views, SignedLink},
+    auth::AuthContext,
+    error::ApiError,
+    models::{ShareLinkCreatedResponse, ShareLinkInfo, ShareTokenResponse},
+    routes::requests::ensure_request_owner,
+    scopes::Scope,
+    util::generate_share_token,
+    AppState,
+};
+
+pub const DEFAULT_LINK_TTL_SECS: i64 = 24 * 3600;
+pub const MAX_LINK_TTL_SECS: i64 = 30 * 24 * 3600;
+
+#[derive(Deserialize, Default)]
+pub struct CreateShareLink {
+    /// Pins the link to one revision; otherwise it follows the latest.
+    pub rev: Option<i32>,
+    pub expires_in_secs: Option<i64>,
+    /// Raw reads the link allows before it answers `410`.
+    pub max_views: Option<i32>,
+}
+
+/// Replaces the share token of an unlisted request; links with the old token stop working.
+pub async fn rotate_share_token(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<ShareTokenResponse>, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    let token: Option<String> = sqlx::query_scalar(
+        "UPDATE requests SET share_token = $3 \
+         WHERE uuid = $1 AND account_id = $2 AND visibility = 'unlisted' \
+         RETURNING share_token",
+    )
+    .bind(uuid)
+    .bind(auth.account_id)
+    .bind(generate_share_token())
+    .fetch_optional(&state.pool)
+    .await?;
+
+    let Some(share_token) = token else {
+        ensure_request_owner(&state, uuid, auth.account_id).await?;
+        return Err(ApiError::BadRequest(
+            "only unlisted requests have a share token".to_string(),
+        ));
+    };
+
+    Ok(Json(ShareTokenResponse { uuid, share_token }))
+}
+
+/// Mints a signed link that reads the request regardless of its visibility until it
+/// expires, is revoked or runs out of views.
+pub async fn create_share_link(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ShareLinkCreatedResponse>), ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    // The body is optional; an empty one mints a 24 hour link to the latest revision.
+    let req: CreateShareLink = if body.is_empty() {
+        CreateShareLink::default()
+    } else {
+        serde_json::from_slice(&body)
+            .map_err(|e| ApiError::BadRequest(format!("invalid share link request: {e}")))?
+    };
+    let ttl = req.expires_in_secs.unwrap_or(DEFAULT_LINK_TTL_SECS);
+    if !(1..=MAX_LINK_TTL_SECS).contains(&ttl) {
+        return Err(ApiError::BadRequest(format!(
+            "expires_in_secs must be between 1 and {MAX_LINK_TTL_SECS}"
+        )));
+    }
+    let max_views = req.max_views.map(validate_max_views).transpose()?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+    if let Some(rev) = req.rev {
+        sqlx::query_scalar::<_, i32>(
+            "SELECT 1 FROM request_revisions WHERE request_uuid = $1 AND rev_number = $2",
+        )
+        .bind(uuid)
+        .bind(rev)
+        .fetch_optional(&state.pool)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    }
+
+    let id = Uuid::new_v4();
+    // Whole seconds, so the stored expiry matches the signed one.
+    let expires_at = DateTime::from_timestamp(Utc::now().timestamp() + ttl, 0)
+        .ok_or_else(|| ApiError::Internal("expiry out of range".to_string()))?;
+    let link = SignedLink::new(&state.share_link_secret, uuid, id, req.rev, expires_at);
+
+    sqlx::query(
+        "INSERT INTO share_links (id, request_uuid, rev, expires_at, views_remaining) \
+         VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(id)
+    .bind(uuid)
+    .bind(req.rev)
+    .bind(expires_at)
+    .bind(max_views)
+    .execute(&state.pool)
+    .await?;
+
+    let query = link.query();
+    Ok((
+        StatusCode::CREATED,
+        Json(ShareLinkCreatedResponse {
+            id,
+            uuid,
+            rev: req.rev,
+            expires_at,
+            views_remaining: max_views,
+            url: format!("/{uuid}?{query}"),
+            view_url: format!("/h/{uuid}?{query}"),
+        }),
+    ))
+}
+
+pub async fn list_share_links(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<Vec<ShareLinkInfo>>, ApiError> {
+    auth.scopes.require(Scope::Read, uuid)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    let rows = sqlx::query_as::<_, ShareLinkInfo>(
+        "SELECT id, rev, expires_at, created_at, revoked_at, views_remaining FROM share_links \
+         WHERE request_uuid = $1 \
+         ORDER BY created_at DESC",
+    )
+    .bind(uuid)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+/// Revokes a link before it expires. Revoked links answer `410`.
+pub async fn revoke_share_link(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path((uuid, id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    let res = sqlx::query(
+        "UPDATE share_links SET revoked_at = COALESCE(revoked_at, now()) \
+         WHERE id = $1 AND request_uuid = $2",
+    )
+    .bind(id)
+    .bind(uuid)
+    .execute(&state.pool)
+    .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
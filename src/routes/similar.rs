@@ -0,0 +1,154 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthContext,
+    error::ApiError,
+    models::{SimilarHint, SimilarRequest},
+    routes::requests::ensure_request_owner,
+    scopes::Scope,
+    simhash::{DEFAULT_MAX_DISTANCE, MAX_DISTANCE},
+    AppState,
+};
+
+/// Header that asks `POST /api/requests` for a near-duplicate hint.
+pub const CHECK_SIMILAR_HEADER: &str = "x-prompt-check-similar";
+
+#[derive(Deserialize)]
+pub struct SimilarQuery {
+    pub max_distance: Option<u32>,
+    pub limit: Option<i64>,
+}
+
+/// The account's requests whose latest revision is a near-duplicate of this one's, closest
+/// first.
+pub async fn get_similar(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Query(q): Query<SimilarQuery>,
+) -> Result<Json<Vec<SimilarRequest>>, ApiError> {
+    auth.scopes.require_all(Scope::Read)?;
+    let max_distance = q.max_distance.unwrap_or(DEFAULT_MAX_DISTANCE);
+    if max_distance > MAX_DISTANCE {
+        return Err(ApiError::BadRequest(format!(
+            "max_distance must be at most {MAX_DISTANCE}"
+        )));
+    }
+    let limit = q.limit.unwrap_or(20).clamp(1, 100);
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    // Requests are compared and described by their newest unexpired revision, as reads of
+    // the latest do.
+    let rows = sqlx::query_as::<_, SimilarRequest>(
+        "WITH target AS ( \
+             SELECT rr.simhash FROM requests r \
+             JOIN request_revisions rr ON rr.request_uuid = r.uuid \
+             WHERE r.uuid = $2 AND rr.rev_number <= r.latest_rev \
+               AND (rr.expires_at IS NULL OR rr.expires_at > now()) \
+             ORDER BY rr.rev_number DESC \
+             LIMIT 1 \
+         ) \
+         SELECT r.uuid, r.created_at, r.updated_at, rr.rev_number AS latest_rev, \
+                rr.content_type as latest_content_type, rr.metadata, \
+                COALESCE(r.title_override, rr.title) AS title, \
+                COALESCE(r.summary_override, rr.summary) AS summary, \
+                ARRAY(SELECT t.tag FROM request_tags t WHERE t.request_uuid = r.uuid ORDER BY t.tag) AS tags, \
+                r.visibility, r.share_token, \
+                r.passphrase_hash IS NOT NULL AS passphrase_protected, r.views_remaining, \
+                r.expires_at, \
+                bit_count((rr.simhash # target.simhash)::bit(64))::int AS distance \
+         FROM requests r \
+         CROSS JOIN target \
+         JOIN LATERAL ( \
+             SELECT rev_number, content_type, metadata, title, summary, simhash \
+             FROM request_revisions \
+             WHERE request_uuid = r.uuid AND rev_number <= r.latest_rev \
+               AND (expires_at IS NULL OR expires_at > now()) \
+             ORDER BY rev_number DESC \
+             LIMIT 1 \
+         ) rr ON true \
+         WHERE r.account_id = $1 AND r.uuid <> $2 \
+           AND (r.expires_at IS NULL OR r.expires_at > now()) \
+           AND bit_count((rr.simhash # target.simhash)::bit(64)) <= $3 \
+         ORDER BY distance, r.updated_at DESC \
+         LIMIT $4",
+    )
+    .bind(auth.account_id)
+    .bind(uuid)
+    .bind(i64::from(max_distance))
+    .bind(limit)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+/// Whether an upload asked for a near-duplicate hint. Off unless the header is `true`.
+pub fn hint_requested(headers: &HeaderMap) -> Result<bool, ApiError> {
+    match headers.get(CHECK_SIMILAR_HEADER).map(|v| v.to_str()) {
+        None => Ok(false),
+        Some(Ok(v)) if v.trim().eq_ignore_ascii_case("true") => Ok(true),
+        Some(Ok(v)) if v.trim().eq_ignore_ascii_case("false") => Ok(false),
+        Some(_) => Err(ApiError::BadRequest(format!(
+            "{CHECK_SIMILAR_HEADER} must be true or false"
+        ))),
+    }
+}
+
+/// Closest near-duplicate of a freshly created request, if any. Best effort: the request is
+/// already stored, so a failed lookup only drops the hint.
+pub async fn hint(state: &AppState, account_id: i64, uuid: Uuid) -> Option<SimilarHint> {
+    let res = sqlx::query_as::<_, (Uuid, i32)>(
+        "WITH target AS (SELECT simhash FROM requests WHERE uuid = $2) \
+         SELECT r.uuid, bit_count((r.simhash # target.simhash)::bit(64))::int AS distance \
+         FROM requests r \
+         CROSS JOIN target \
+         WHERE r.account_id = $1 AND r.uuid <> $2 \
+           AND (r.expires_at IS NULL OR r.expires_at > now()) \
+           AND bit_count((r.simhash # target.simhash)::bit(64)) <= $3 \
+         ORDER BY distance, r.updated_at DESC \
+         LIMIT 1",
+    )
+    .bind(account_id)
+    .bind(uuid)
+    .bind(i64::from(DEFAULT_MAX_DISTANCE))
+    .fetch_optional(&state.pool)
+    .await;
+
+    match res {
+        Ok(row) => row.map(|(similar, distance)| SimilarHint {
+            uuid: similar,
+            distance,
+            message: format!(
+                "this looks like an update to request {similar}; consider PUT /api/requests/{similar} instead"
+            ),
+        }),
+        Err(err) => {
+            tracing::warn!("similarity lookup failed for {}: {}", uuid, err);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hint_is_opt_in() {
+        let mut headers = HeaderMap::new();
+        assert!(!hint_requested(&headers).unwrap());
+        headers.insert(CHECK_SIMILAR_HEADER, "true".parse().unwrap());
+        assert!(hint_requested(&headers).unwrap());
+        headers.insert(CHECK_SIMILAR_HEADER, "false".parse().unwrap());
+        assert!(!hint_requested(&headers).unwrap());
+        headers.insert(CHECK_SIMILAR_HEADER, "yes".parse().unwrap());
+        assert!(hint_requested(&headers).is_err());
+    }
+}
@@ -0,0 +1,197 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthContext,
+    error::ApiError,
+    models::{RequestTags, TagCount},
+    routes::requests::ensure_request_owner,
+    scopes::Scope,
+    AppState,
+};
+
+pub const MAX_TAG_LEN: usize = 64;
+pub const MAX_TAGS_PER_REQUEST: usize = 32;
+
+/// Lowercases and validates a tag. Tags are 1-64 characters of `a-z`, `0-9`, `-`, `_`,
+/// `.` and `:`, so `eval-run-42` and `customer:acme` are fine but spaces are not.
+pub fn normalize(raw: &str) -> Result<String, ApiError> {
+    let tag = raw.trim().to_ascii_lowercase();
+    let valid = !tag.is_empty()
+        && tag.len() <= MAX_TAG_LEN
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    if !valid {
+        return Err(ApiError::BadRequest(format!("invalid tag: {raw}")));
+    }
+    Ok(tag)
+}
+
+/// Normalizes a list of tags, dropping duplicates while keeping the first occurrence.
+pub fn normalize_all<S: AsRef<str>>(raw: &[S]) -> Result<Vec<String>, ApiError> {
+    let mut tags: Vec<String> = Vec::with_capacity(raw.len());
+    for tag in raw {
+        let tag = normalize(tag.as_ref())?;
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
+
+/// How a multi-tag filter combines: `all` requires every tag, `any` at least one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TagMode {
+    All,
+    Any,
+}
+
+impl TagMode {
+    pub fn parse(raw: Option<&str>) -> Result<Self, ApiError> {
+        match raw {
+            None | Some("all") => Ok(TagMode::All),
+            Some("any") => Ok(TagMode::Any),
+            Some(other) => Err(ApiError::BadRequest(format!(
+                "tag_mode must be all or any, got {other}"
+            ))),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AddTagsRequest {
+    pub tags: Vec<String>,
+}
+
+pub async fn list_request_tags(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+) -> Result<Json<RequestTags>, ApiError> {
+    auth.scopes.require(Scope::Read, uuid)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+    Ok(Json(request_tags(&state, uuid).await?))
+}
+
+pub async fn add_request_tags(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path(uuid): Path<Uuid>,
+    Json(req): Json<AddTagsRequest>,
+) -> Result<Json<RequestTags>, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    let new_tags = normalize_all(&req.tags)?;
+    if new_tags.is_empty() {
+        return Err(ApiError::BadRequest("tags must not be empty".to_string()));
+    }
+
+    let mut tx = state.pool.begin().await?;
+
+    sqlx::query_scalar::<_, i32>(
+        "SELECT 1 FROM requests WHERE uuid = $1 AND account_id = $2 FOR UPDATE",
+    )
+    .bind(uuid)
+    .bind(auth.account_id)
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or(ApiError::NotFound)?;
+
+    sqlx::query(
+        "INSERT INTO request_tags (request_uuid, tag) \
+         SELECT $1, unnest($2::text[]) \
+         ON CONFLICT DO NOTHING",
+    )
+    .bind(uuid)
+    .bind(&new_tags)
+    .execute(&mut *tx)
+    .await?;
+
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM request_tags WHERE request_uuid = $1")
+            .bind(uuid)
+            .fetch_one(&mut *tx)
+            .await?;
+    if count > MAX_TAGS_PER_REQUEST as i64 {
+        return Err(ApiError::BadRequest(format!(
+            "a request can have at most {MAX_TAGS_PER_REQUEST} tags"
+        )));
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(request_tags(&state, uuid).await?))
+}
+
+pub async fn remove_request_tag(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Path((uuid, tag)): Path<(Uuid, String)>,
+) -> Result<StatusCode, ApiError> {
+    auth.scopes.require(Scope::Update, uuid)?;
+    let tag = normalize(&tag)?;
+    ensure_request_owner(&state, uuid, auth.account_id).await?;
+
+    let res = sqlx::query("DELETE FROM request_tags WHERE request_uuid = $1 AND tag = $2")
+        .bind(uuid)
+        .bind(&tag)
+        .execute(&state.pool)
+        .await?;
+    if res.rows_affected() == 0 {
+        return Err(ApiError::NotFound);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn list_tags(
+    State(state): State<AppState>,
+    auth: AuthContext,
+) -> Result<Json<Vec<TagCount>>, ApiError> {
+    auth.scopes.require_all(Scope::Read)?;
+    let rows = sqlx::query_as::<_, TagCount>(
+        "SELECT t.tag, COUNT(*) AS count \
+         FROM request_tags t \
+         JOIN requests r ON r.uuid = t.request_uuid \
+         WHERE r.account_id = $1 \
+         GROUP BY t.tag \
+         ORDER BY count DESC, t.tag",
+    )
+    .bind(auth.account_id)
+    .fetch_all(&state.pool)
+    .await?;
+
+    Ok(Json(rows))
+}
+
+async fn request_tags(state: &AppState, uuid: Uuid) -> Result<RequestTags, ApiError> {
+    let tags =
+        sqlx::query_scalar("SELECT tag FROM request_tags WHERE request_uuid = $1 ORDER BY tag")
+            .bind(uuid)
+            .fetch_all(&state.pool)
+            .await?;
+    Ok(RequestTags { uuid, tags })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_and_dedupes() {
+        let tags = normalize_all(&[" BugFix ", "customer-x", "bugfix", "eval:run.42"]).unwrap();
+        assert_eq!(tags, vec!["bugfix", "customer-x", "eval:run.42"]);
+    }
+
+    #[test]
+    fn rejects_invalid_tags() {
+        assert!(normalize("").is_err());
+        assert!(normalize("two words").is_err());
+        assert!(normalize(&"a".repeat(MAX_TAG_LEN + 1)).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+use axum::{extract::State, http::StatusCode, Json};
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    auth::AuthContext,
+    error::ApiError,
+    models::UploadTokenResponse,
+    routes::requests::ensure_request_owner,
+    scopes::{Scope, Scopes},
+    tokens::{UploadToken, DEFAULT_TOKEN_TTL_SECS, MAX_TOKEN_TTL_SECS},
+    AppState,
+};
+
+#[derive(Deserialize)]
+pub struct CreateToken {
+    /// Binds the token to this one request.
+    pub request_uuid: Option<Uuid>,
+    /// Defaults to the key's create and update scopes, or just update for a bound token.
+    pub scopes: Option<Vec<String>>,
+    pub expires_in_secs: Option<i64>,
+}
+
+/// Mints an upload token from the calling key. The token can do at most what the key can,
+/// and a key limited to some requests can only mint tokens bound to one of them.
+pub async fn create_token(
+    State(state): State<AppState>,
+    auth: AuthContext,
+    Json(req): Json<CreateToken>,
+) -> Result<(StatusCode, Json<UploadTokenResponse>), ApiError> {
+    if auth.via_token {
+        return Err(ApiError::Forbidden(
+            "upload tokens cannot mint tokens; use an API key".to_string(),
+        ));
+    }
+
+    let ttl = req.expires_in_secs.unwrap_or(DEFAULT_TOKEN_TTL_SECS);
+    if !(1..=MAX_TOKEN_TTL_SECS).contains(&ttl) {
+        return Err(ApiError::BadRequest(format!(
+            "expires_in_secs must be between 1 and {MAX_TOKEN_TTL_SECS}"
+        )));
+    }
+
+    let scopes = token_scopes(
+        &auth.scopes,
+        req.scopes.as_deref(),
+        req.request_uuid.is_some(),
+    )?;
+    match req.request_uuid {
+        Some(uuid) => {
+            for scope in &scopes {
+                auth.scopes.require(*scope, uuid)?;
+            }
+            ensure_request_owner(&state, uuid, auth.account_id).await?;
+        }
+        None if auth.scopes.requests().is_some() => {
+            return Err(ApiError::BadRequest(
+                "this key is limited to specific requests; set request_uuid".to_string(),
+            ));
+        }
+        None => {}
+    }
+
+    let expires_at = Utc::now() + Duration::seconds(ttl);
+    let token = UploadToken::new(
+        auth.account_id,
+        auth.key_id,
+        req.request_uuid,
+        &scopes,
+        expires_at,
+    );
+    let signed = token.sign(&state.token_signing_key)?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(UploadTokenResponse {
+            token: signed,
+            scopes: token.scopes,
+            request_uuid: req.request_uuid,
+            expires_at,
+        }),
+    ))
+}
+
+/// A token bound to one request cannot create requests, since nothing would keep it to that
+/// one: it could upload without limit.
+fn token_scopes(
+    key: &Scopes,
+    names: Option<&[String]>,
+    bound: bool,
+) -> Result<Vec<Scope>, ApiError> {
+    let scopes: Vec<Scope> = match names {
+        Some(_) => Scopes::from_request(names, None)?.scopes().to_vec(),
+        None => [Scope::Create, Scope::Update]
+            .into_iter()
+            .filter(|scope| key.has(*scope) && !(bound && *scope == Scope::Create))
+            .collect(),
+    };
+    if bound && scopes.contains(&Scope::Create) {
+        return Err(ApiError::BadRequest(
+            "tokens bound to a request cannot have requests:create".to_string(),
+        ));
+    }
+    if scopes.is_empty() {
+        return Err(ApiError::Forbidden(
+            "this key has no upload scopes; set scopes explicitly".to_string(),
+        ));
+    }
+    for scope in &scopes {
+        key.require_scope(*scope)?;
+    }
+    Ok(scopes)
+}
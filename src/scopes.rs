@@ -0,0 +1,191 @@
+use uuid::Uuid;
+
+use crate::error::ApiError;
+
+/// Request UUIDs a single key can be limited to.
+pub const MAX_KEY_REQUESTS: usize = 100;
+
+/// One permission an API key can carry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Create,
+    Update,
+    Read,
+    Delete,
+}
+
+impl Scope {
+    pub const ALL: [Scope; 4] = [Scope::Create, Scope::Update, Scope::Read, Scope::Delete];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Scope::Create => "requests:create",
+            Scope::Update => "requests:update",
+            Scope::Read => "requests:read",
+            Scope::Delete => "requests:delete",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Result<Self, ApiError> {
+        Scope::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == raw)
+            .ok_or_else(|| ApiError::BadRequest(format!("unknown scope: {raw}")))
+    }
+}
+
+/// What a credential may do: a set of scopes, optionally limited to some requests.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Scopes {
+    scopes: Vec<Scope>,
+    /// `None` means every request of the account.
+    requests: Option<Vec<Uuid>>,
+}
+
+impl Scopes {
+    pub fn new(scopes: Vec<Scope>, requests: Option<Vec<Uuid>>) -> Self {
+        Self { scopes, requests }
+    }
+
+    pub fn full() -> Self {
+        Self::new(Scope::ALL.to_vec(), None)
+    }
+
+    /// Builds scopes from stored names, skipping any this version does not know.
+    pub fn from_names(names: &[String], requests: Option<Vec<Uuid>>) -> Self {
+        let scopes = names
+            .iter()
+            .filter_map(|name| Scope::parse(name).ok())
+            .collect();
+        Self::new(scopes, requests)
+    }
+
+    /// Parses the scopes and request list of a new key. Without scopes, the key gets all.
+    pub fn from_request(
+        names: Option<&[String]>,
+        requests: Option<Vec<Uuid>>,
+    ) -> Result<Self, ApiError> {
+        let scopes = match names {
+            Some([]) => return Err(ApiError::BadRequest("scopes cannot be empty".to_string())),
+            Some(names) => {
+                let mut scopes = Vec::new();
+                for name in names {
+                    let scope = Scope::parse(name)?;
+                    if !scopes.contains(&scope) {
+                        scopes.push(scope);
+                    }
+                }
+                scopes
+            }
+            None => Scope::ALL.to_vec(),
+        };
+        if let Some(requests) = &requests {
+            if requests.is_empty() || requests.len() > MAX_KEY_REQUESTS {
+                return Err(ApiError::BadRequest(format!(
+                    "request_uuids must list 1-{MAX_KEY_REQUESTS} requests"
+                )));
+            }
+        }
+        Ok(Self::new(scopes, requests))
+    }
+
+    pub fn scopes(&self) -> &[Scope] {
+        &self.scopes
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.scopes.iter().map(|s| s.as_str().to_string()).collect()
+    }
+
+    pub fn requests(&self) -> Option<&[Uuid]> {
+        self.requests.as_deref()
+    }
+
+    /// Every scope on every request: what managing keys and account settings need.
+    pub fn is_full(&self) -> bool {
+        self.requests.is_none() && Scope::ALL.iter().all(|s| self.scopes.contains(s))
+    }
+
+    pub fn has(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope)
+    }
+
+    /// For calls on one existing request.
+    pub fn require(&self, scope: Scope, uuid: Uuid) -> Result<(), ApiError> {
+        self.require_scope(scope)?;
+        match &self.requests {
+            Some(requests) if !requests.contains(&uuid) => Err(ApiError::Forbidden(format!(
+                "this key's {} scope does not cover request {uuid}",
+                scope.as_str()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// For calls that span all of the account's requests, such as lists and search.
+    pub fn require_all(&self, scope: Scope) -> Result<(), ApiError> {
+        self.require_scope(scope)?;
+        if self.requests.is_some() {
+            return Err(ApiError::Forbidden(format!(
+                "{} is limited to specific requests by this key",
+                scope.as_str()
+            )));
+        }
+        Ok(())
+    }
+
+    /// For calls that do not touch an existing request, such as creating one.
+    pub fn require_scope(&self, scope: Scope) -> Result<(), ApiError> {
+        if !self.has(scope) {
+            return Err(ApiError::Forbidden(format!(
+                "missing scope {}",
+                scope.as_str()
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn require_full(&self) -> Result<(), ApiError> {
+        if !self.is_full() {
+            return Err(ApiError::Forbidden(
+                "this needs a key with every scope and no request limit".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_scope_names() {
+        let names = vec!["requests:create".to_string(), "requests:create".to_string()];
+        let scopes = Scopes::from_request(Some(&names), None).unwrap();
+        assert_eq!(scopes.names(), vec!["requests:create"]);
+        assert!(Scopes::from_request(Some(&[]), None).is_err());
+        assert!(Scopes::from_request(Some(&["requests:admin".to_string()]), None).is_err());
+        assert!(Scopes::from_request(None, None).unwrap().is_full());
+        assert!(Scopes::from_request(None, Some(vec![])).is_err());
+    }
+
+    #[test]
+    fn enforces_scopes_and_request_limits() {
+        let allowed = Uuid::new_v4();
+        let scopes = Scopes::new(vec![Scope::Create, Scope::Update], Some(vec![allowed]));
+        assert!(scopes.require_scope(Scope::Create).is_ok());
+        assert!(scopes.require(Scope::Update, allowed).is_ok());
+        assert!(matches!(
+            scopes.require(Scope::Update, Uuid::new_v4()),
+            Err(ApiError::Forbidden(_))
+        ));
+        match scopes.require(Scope::Delete, allowed) {
+            Err(ApiError::Forbidden(msg)) => assert!(msg.contains("requests:delete")),
+            other => panic!("expected forbidden, got {other:?}"),
+        }
+        assert!(scopes.require_all(Scope::Update).is_err());
+        assert!(!scopes.is_full());
+        assert!(Scopes::full().require_all(Scope::Read).is_ok());
+    }
+}
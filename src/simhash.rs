@@ -0,0 +1,92 @@
+/// Fingerprints within this many differing bits are reported as near-duplicates by default.
+pub const DEFAULT_MAX_DISTANCE: u32 = 8;
+/// Upper bound for caller-supplied distances; beyond this unrelated bodies start to match.
+pub const MAX_DISTANCE: u32 = 16;
+
+/// 64-bit SimHash over the body's non-empty lines, lowercased with whitespace collapsed.
+/// Bodies that differ by a few added or edited lines land within a small Hamming distance of
+/// each other. Returns `None` for bodies without any text.
+pub fn fingerprint(text: &str) -> Option<i64> {
+    let mut weights = [0i64; 64];
+    let mut features = 0;
+    for line in text.lines() {
+        let normalized = line
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if normalized.is_empty() {
+            continue;
+        }
+        features += 1;
+        let hash = feature_hash(normalized.as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            if hash >> bit & 1 == 1 {
+                *weight += 1;
+            } else {
+                *weight -= 1;
+            }
+        }
+    }
+    if features == 0 {
+        return None;
+    }
+
+    let mut out = 0u64;
+    for (bit, weight) in weights.iter().enumerate() {
+        if *weight > 0 {
+            out |= 1 << bit;
+        }
+    }
+    Some(out as i64)
+}
+
+/// Number of differing bits between two fingerprints.
+pub fn distance(a: i64, b: i64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// FNV-1a followed by a SplitMix64 finalizer. Fingerprints are stored, so this must stay
+/// stable across releases (unlike `std`'s hashers).
+fn feature_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(turns: usize) -> String {
+        (0..turns)
+            .map(|i| format!("{{\"role\":\"user\",\"content\":\"step {i} of the refactor\"}}\n"))
+            .collect()
+    }
+
+    #[test]
+    fn grown_sessions_stay_close() {
+        let base = fingerprint(&session(40)).unwrap();
+        let grown = fingerprint(&session(44)).unwrap();
+        assert!(distance(base, grown) <= DEFAULT_MAX_DISTANCE);
+
+        let reformatted = fingerprint(&session(40).to_uppercase().replace(' ', "   ")).unwrap();
+        assert_eq!(base, reformatted);
+    }
+
+    #[test]
+    fn unrelated_bodies_are_far_apart() {
+        let a = fingerprint(&session(40)).unwrap();
+        let other: String = (0..40)
+            .map(|i| format!("# Section {i}\nnotes about the deploy pipeline, item {i}\n"))
+            .collect();
+        let b = fingerprint(&other).unwrap();
+        assert!(distance(a, b) > MAX_DISTANCE);
+        assert!(fingerprint("\n  \n").is_none());
+    }
+}
@@ -15,8 +15,8 @@ pub struct S3Store {
 
 impl S3Store {
     pub async fn new(cfg: &Config) -> Result<Self, Box<dyn std::error::Error>> {
-        let mut loader =
-            aws_config::defaults(BehaviorVersion::latest()).region(Region::new(cfg.s3_region.clone()));
+        let mut loader = aws_config::defaults(BehaviorVersion::latest())
+            .region(Region::new(cfg.s3_region.clone()));
 
         if let Some(endpoint) = &cfg.s3_endpoint {
             loader = loader.endpoint_url(endpoint);
@@ -87,8 +87,7 @@ impl S3Store {
             }
         }
 
-        Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
+        Err(Box::new(std::io::Error::other(
             last_err.unwrap_or_else(|| "s3 bucket check failed".to_string()),
         )))
     }
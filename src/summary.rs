@@ -0,0 +1,180 @@
+use serde_json::Value;
+
+use crate::{
+    frontmatter,
+    transcript::{self, parse_messages},
+    util::ContentKind,
+};
+
+pub const MAX_TITLE_CHARS: usize = 120;
+pub const MAX_SUMMARY_CHARS: usize = 280;
+
+/// Title for a revision: the first heading for Markdown (front matter `title:` reaches the
+/// metadata instead), the first line of the first user message for JSONL.
+pub fn title(kind: ContentKind, body: &str) -> Option<String> {
+    let raw = match kind {
+        ContentKind::Markdown => first_heading(frontmatter::strip(body)),
+        ContentKind::Jsonl => parse_messages(body)
+            .iter()
+            .filter(|m| transcript::role(&m.value) == Some("user"))
+            .map(|m| transcript::text(&m.value))
+            .find_map(|text| {
+                text.lines()
+                    .find(|line| !line.trim().is_empty())
+                    .map(str::to_string)
+            }),
+    }?;
+    clip(&raw, MAX_TITLE_CHARS)
+}
+
+/// Short plain-text preview: the prose of a Markdown document, or the user and assistant
+/// text of a transcript, with whitespace collapsed.
+pub fn summary(kind: ContentKind, body: &str) -> Option<String> {
+    let mut out = String::new();
+    let mut push = |text: &str| {
+        if out.chars().count() > MAX_SUMMARY_CHARS {
+            return;
+        }
+        if !out.is_empty() {
+            out.push(' ');
+        }
+        out.push_str(text);
+    };
+
+    match kind {
+        ContentKind::Markdown => {
+            let mut in_fence = false;
+            for line in frontmatter::strip(body).lines() {
+                let line = line.trim();
+                if line.starts_with("```") || line.starts_with("~~~") {
+                    in_fence = !in_fence;
+                    continue;
+                }
+                if in_fence || line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                push(&strip_markdown(line));
+            }
+        }
+        ContentKind::Jsonl => {
+            for message in parse_messages(body) {
+                if matches!(
+                    transcript::role(&message.value),
+                    Some("user") | Some("assistant")
+                ) {
+                    let text = transcript::text(&message.value);
+                    if !text.trim().is_empty() {
+                        push(&text);
+                    }
+                }
+            }
+        }
+    }
+    clip(&out, MAX_SUMMARY_CHARS)
+}
+
+/// A caller-supplied title from upload metadata, if it has one.
+pub fn metadata_title(metadata: &serde_json::Map<String, Value>) -> Option<String> {
+    metadata
+        .get("title")
+        .and_then(Value::as_str)
+        .and_then(|title| clip(title, MAX_TITLE_CHARS))
+}
+
+fn first_heading(body: &str) -> Option<String> {
+    let mut in_fence = false;
+    for line in body.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let text = line.trim_start_matches('#');
+        if line.starts_with('#') && (text.is_empty() || text.starts_with(' ')) {
+            let text = text.trim().trim_end_matches('#').trim();
+            if !text.is_empty() {
+                return Some(strip_markdown(text));
+            }
+        }
+    }
+    None
+}
+
+/// Drops list and quote markers and inline emphasis characters from a Markdown line.
+fn strip_markdown(line: &str) -> String {
+    let line = line.trim_start_matches(['>', ' ']);
+    let line = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))
+        .unwrap_or(line);
+    line.chars().filter(|c| !matches!(c, '*' | '`')).collect()
+}
+
+/// Collapses whitespace and cuts to `max` characters, marking a cut with an ellipsis.
+fn clip(text: &str, max: usize) -> Option<String> {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    if collapsed.chars().count() <= max {
+        return Some(collapsed);
+    }
+    let mut out: String = collapsed.chars().take(max - 1).collect();
+    out.truncate(out.trim_end().len());
+    out.push('…');
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn markdown_title_skips_front_matter() {
+        let body =
+            "---\ntitle: \"Fix the flaky test\"\nmodel: opus\n---\n# Heading\n\nBody text.\n";
+        assert_eq!(
+            title(ContentKind::Markdown, body).as_deref(),
+            Some("Heading")
+        );
+        assert_eq!(
+            summary(ContentKind::Markdown, body).as_deref(),
+            Some("Body text.")
+        );
+
+        let body = "```\n# not a heading\n```\n## Real *heading*\n";
+        assert_eq!(
+            title(ContentKind::Markdown, body).as_deref(),
+            Some("Real heading")
+        );
+    }
+
+    #[test]
+    fn jsonl_title_is_first_user_message() {
+        let body = "{\"type\":\"system\",\"content\":\"init\"}\n\
+            {\"role\":\"user\",\"content\":[{\"type\":\"tool_result\",\"content\":\"x\"}]}\n\
+            {\"role\":\"user\",\"content\":\"\\n  Why does   the build fail?\\nmore\"}\n\
+            {\"role\":\"assistant\",\"content\":\"Checking.\"}\n";
+        assert_eq!(
+            title(ContentKind::Jsonl, body).as_deref(),
+            Some("Why does the build fail?")
+        );
+        assert_eq!(
+            summary(ContentKind::Jsonl, body).as_deref(),
+            Some("Why does the build fail? more Checking.")
+        );
+    }
+
+    #[test]
+    fn long_text_is_clipped() {
+        let body = format!("# {}\n", "word ".repeat(100));
+        let title = title(ContentKind::Markdown, &body).unwrap();
+        assert_eq!(title.chars().count(), MAX_TITLE_CHARS);
+        assert!(title.ends_with('…'));
+        assert!(summary(ContentKind::Markdown, "# only a heading\n").is_none());
+    }
+}
@@ -0,0 +1,140 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use chrono::{DateTime, Utc};
+use hmac::Mac;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    error::ApiError,
+    scopes::{Scope, Scopes},
+    util::hmac_sha256,
+};
+
+/// Upload tokens look like `prt_<claims>.<signature>`, both base64url.
+pub const TOKEN_PREFIX: &str = "prt_";
+pub const DEFAULT_TOKEN_TTL_SECS: i64 = 3600;
+pub const MAX_TOKEN_TTL_SECS: i64 = 24 * 3600;
+
+/// A short-lived bearer credential minted from an API key. Everything needed to authorize
+/// it is in the signed claims, so using one costs no key lookup.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadToken {
+    #[serde(rename = "a")]
+    pub account_id: i64,
+    /// The key that minted the token.
+    #[serde(rename = "k")]
+    pub key_id: Uuid,
+    #[serde(rename = "r", default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<Uuid>,
+    #[serde(rename = "s")]
+    pub scopes: Vec<String>,
+    /// Unix seconds.
+    #[serde(rename = "e")]
+    pub expires: i64,
+}
+
+impl UploadToken {
+    pub fn new(
+        account_id: i64,
+        key_id: Uuid,
+        request: Option<Uuid>,
+        scopes: &[Scope],
+        expires_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            account_id,
+            key_id,
+            request,
+            scopes: scopes.iter().map(|s| s.as_str().to_string()).collect(),
+            expires: expires_at.timestamp(),
+        }
+    }
+
+    pub fn sign(&self, secret: &str) -> Result<String, ApiError> {
+        let claims = serde_json::to_vec(self).map_err(|e| ApiError::Internal(e.to_string()))?;
+        let claims = URL_SAFE_NO_PAD.encode(claims);
+        let sig = hmac_sha256(
+            secret.as_bytes(),
+            &[TOKEN_PREFIX.as_bytes(), claims.as_bytes()],
+        );
+        let sig = URL_SAFE_NO_PAD.encode(sig.finalize().into_bytes());
+        Ok(format!("{TOKEN_PREFIX}{claims}.{sig}"))
+    }
+
+    /// Checks the signature and expiry of `token`. Every failure is `Unauthorized`, so a
+    /// caller learns nothing about why a token was refused.
+    pub fn verify(token: &str, secret: &str, now: DateTime<Utc>) -> Result<Self, ApiError> {
+        let (claims, sig) = token
+            .strip_prefix(TOKEN_PREFIX)
+            .and_then(|rest| rest.split_once('.'))
+            .ok_or(ApiError::Unauthorized)?;
+        let sig = URL_SAFE_NO_PAD
+            .decode(sig)
+            .map_err(|_| ApiError::Unauthorized)?;
+        hmac_sha256(
+            secret.as_bytes(),
+            &[TOKEN_PREFIX.as_bytes(), claims.as_bytes()],
+        )
+        .verify_slice(&sig)
+        .map_err(|_| ApiError::Unauthorized)?;
+
+        let claims = URL_SAFE_NO_PAD
+            .decode(claims)
+            .map_err(|_| ApiError::Unauthorized)?;
+        let token: Self = serde_json::from_slice(&claims).map_err(|_| ApiError::Unauthorized)?;
+        if token.expires <= now.timestamp() {
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(token)
+    }
+
+    pub fn scopes(&self) -> Scopes {
+        Scopes::from_names(&self.scopes, self.request.map(|uuid| vec![uuid]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(expires_at: DateTime<Utc>) -> UploadToken {
+        UploadToken::new(
+            7,
+            Uuid::new_v4(),
+            Some(Uuid::new_v4()),
+            &[Scope::Update],
+            expires_at,
+        )
+    }
+
+    #[test]
+    fn round_trips_and_rejects_tampering() {
+        let now = Utc::now();
+        let minted = token(now + chrono::Duration::seconds(60));
+        let signed = minted.sign("secret").unwrap();
+        assert!(signed.starts_with(TOKEN_PREFIX));
+        assert_eq!(UploadToken::verify(&signed, "secret", now).unwrap(), minted);
+        assert!(UploadToken::verify(&signed, "other", now).is_err());
+
+        let forged = UploadToken {
+            account_id: 8,
+            ..minted.clone()
+        }
+        .sign("other")
+        .unwrap();
+        let (_, sig) = signed.split_once('.').unwrap();
+        let (claims, _) = forged.split_once('.').unwrap();
+        assert!(UploadToken::verify(&format!("{claims}.{sig}"), "secret", now).is_err());
+        assert!(UploadToken::verify("prq_abc", "secret", now).is_err());
+    }
+
+    #[test]
+    fn expired_tokens_are_refused() {
+        let now = Utc::now();
+        let signed = token(now).sign("secret").unwrap();
+        assert!(matches!(
+            UploadToken::verify(&signed, "secret", now),
+            Err(ApiError::Unauthorized)
+        ));
+    }
+}
@@ -0,0 +1,312 @@
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{error::ApiError, util::ContentKind};
+
+/// Upper bound on indexed text per request; `tsvector` values are limited to 1 MB.
+pub const MAX_SEARCH_TEXT_BYTES: usize = 512 * 1024;
+
+/// One non-empty line of a JSONL transcript, with its 1-based line number in the source.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    pub line: usize,
+    pub value: Value,
+}
+
+/// A message as returned to clients, with its 1-based position among messages.
+#[derive(Debug, Serialize)]
+pub struct MessageEntry {
+    pub index: usize,
+    pub line: usize,
+    pub message: Value,
+}
+
+/// Splits a JSONL body into messages. Lines that are not valid JSON are kept as strings
+/// so a single malformed line does not hide the rest of the transcript.
+pub fn parse_messages(text: &str) -> Vec<Message> {
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| Message {
+            line: idx + 1,
+            value: serde_json::from_str(line).unwrap_or_else(|_| Value::String(line.to_string())),
+        })
+        .collect()
+}
+
+/// The speaker of a message, for both plain chat records (`{"role": ...}`) and agent logs
+/// that wrap the chat message (`{"type": "user", "message": {"role": ...}}`).
+pub fn role(value: &Value) -> Option<&str> {
+    value
+        .get("role")
+        .or_else(|| value.get("message").and_then(|m| m.get("role")))
+        .and_then(Value::as_str)
+}
+
+/// The `content` of a message, looking through a wrapping `message` object if present.
+pub fn content(value: &Value) -> Option<&Value> {
+    value
+        .get("content")
+        .or_else(|| value.get("message").and_then(|m| m.get("content")))
+}
+
+/// The human-readable text of a message: string content, or the `text` blocks of
+/// block-structured content, joined by blank lines.
+pub fn text(value: &Value) -> String {
+    match content(value) {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Array(blocks)) => blocks
+            .iter()
+            .filter_map(|block| match block {
+                Value::String(s) => Some(s.as_str()),
+                Value::Object(_) if block.get("type").and_then(Value::as_str) == Some("text") => {
+                    block.get("text").and_then(Value::as_str)
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        _ => String::new(),
+    }
+}
+
+/// Text to index for full-text search. Markdown is indexed as-is; for JSONL every string
+/// under a message's content (text, tool input, tool results) is indexed, falling back to
+/// all strings in the record when it has no recognizable content.
+pub fn search_text(kind: ContentKind, body: &str) -> String {
+    let mut out = match kind {
+        ContentKind::Markdown => body.to_string(),
+        ContentKind::Jsonl => {
+            let mut out = String::new();
+            for message in parse_messages(body) {
+                let root = content(&message.value).unwrap_or(&message.value);
+                collect_strings(root, &mut out);
+                if out.len() > MAX_SEARCH_TEXT_BYTES {
+                    break;
+                }
+            }
+            out
+        }
+    };
+    truncate_at_char_boundary(&mut out, MAX_SEARCH_TEXT_BYTES);
+    out
+}
+
+fn collect_strings(value: &Value, out: &mut String) {
+    match value {
+        Value::String(s) => {
+            out.push_str(s);
+            out.push('\n');
+        }
+        Value::Array(items) => items.iter().for_each(|v| collect_strings(v, out)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, out)),
+        _ => {}
+    }
+}
+
+pub fn truncate_at_char_boundary(s: &mut String, max: usize) {
+    if s.len() > max {
+        let mut end = max;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+    }
+}
+
+/// An inclusive, 1-based range such as `10-20`, `10-` (to the end) or `10`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Range {
+    pub start: usize,
+    pub end: Option<usize>,
+}
+
+impl Range {
+    pub fn parse(raw: &str) -> Result<Self, ApiError> {
+        let invalid = || ApiError::BadRequest(format!("invalid range: {raw}"));
+        let parse = |s: &str| s.trim().parse::<usize>().map_err(|_| invalid());
+
+        let (start, end) = match raw.split_once('-') {
+            Some((start, "")) => (parse(start)?, None),
+            Some((start, end)) => (parse(start)?, Some(parse(end)?)),
+            None => {
+                let n = parse(raw)?;
+                (n, Some(n))
+            }
+        };
+        if start < 1 || end.is_some_and(|end| end < start) {
+            return Err(invalid());
+        }
+        Ok(Self { start, end })
+    }
+
+    /// Clamps the range to `total` items, returning 0-based `start..end` bounds.
+    pub fn bounds(&self, total: usize) -> std::ops::Range<usize> {
+        let end = self.end.unwrap_or(total).min(total);
+        let start = (self.start - 1).min(end);
+        start..end
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RangeKind {
+    Lines,
+    Messages,
+}
+
+impl RangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RangeKind::Lines => "lines",
+            RangeKind::Messages => "messages",
+        }
+    }
+}
+
+/// Raw text of a line or message window, one source line per output line.
+pub fn excerpt(text: &str, kind: RangeKind, range: Range) -> String {
+    let selected: Vec<&str> = match kind {
+        RangeKind::Lines => slice_lines(text, range).lines,
+        RangeKind::Messages => {
+            let all: Vec<&str> = text.lines().collect();
+            slice_messages(text, range)
+                .messages
+                .iter()
+                .map(|m| all[m.line - 1])
+                .collect()
+        }
+    };
+
+    let mut out = String::new();
+    for line in selected {
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// A window of lines from a text body.
+pub struct LineSlice<'a> {
+    pub total: usize,
+    pub start: usize,
+    pub lines: Vec<&'a str>,
+}
+
+pub fn slice_lines(text: &str, range: Range) -> LineSlice<'_> {
+    let lines: Vec<&str> = text.lines().collect();
+    let bounds = range.bounds(lines.len());
+    LineSlice {
+        total: lines.len(),
+        start: bounds.start + 1,
+        lines: lines[bounds].to_vec(),
+    }
+}
+
+/// A window of messages from a JSONL body.
+pub struct MessageSlice {
+    pub total: usize,
+    pub start: usize,
+    pub messages: Vec<Message>,
+}
+
+pub fn slice_messages(text: &str, range: Range) -> MessageSlice {
+    let mut messages = parse_messages(text);
+    let total = messages.len();
+    let bounds = range.bounds(total);
+    let start = bounds.start + 1;
+    messages.truncate(bounds.end);
+    messages.drain(..bounds.start);
+    MessageSlice {
+        total,
+        start,
+        messages,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_text_from_wrapped_and_plain_messages() {
+        let plain: Value = serde_json::json!({"role": "user", "content": "fix the migration"});
+        assert_eq!(role(&plain), Some("user"));
+        assert_eq!(text(&plain), "fix the migration");
+
+        let wrapped: Value = serde_json::json!({
+            "type": "assistant",
+            "message": {
+                "role": "assistant",
+                "content": [
+                    {"type": "text", "text": "Looking now."},
+                    {"type": "tool_use", "name": "bash", "input": {"command": "ls"}}
+                ]
+            }
+        });
+        assert_eq!(role(&wrapped), Some("assistant"));
+        assert_eq!(text(&wrapped), "Looking now.");
+    }
+
+    #[test]
+    fn search_text_includes_tool_content() {
+        let body = "{\"role\":\"assistant\",\"content\":[{\"type\":\"tool_use\",\"input\":{\"command\":\"cargo test\"}}]}\n";
+        let text = search_text(ContentKind::Jsonl, body);
+        assert!(text.contains("cargo test"));
+        assert!(!text.contains("assistant"));
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(
+            Range::parse("3-5").unwrap(),
+            Range {
+                start: 3,
+                end: Some(5)
+            }
+        );
+        assert_eq!(
+            Range::parse("3-").unwrap(),
+            Range {
+                start: 3,
+                end: None
+            }
+        );
+        assert_eq!(
+            Range::parse("4").unwrap(),
+            Range {
+                start: 4,
+                end: Some(4)
+            }
+        );
+        assert!(Range::parse("0-2").is_err());
+        assert!(Range::parse("5-3").is_err());
+        assert!(Range::parse("a-b").is_err());
+    }
+
+    #[test]
+    fn slices_are_clamped() {
+        let text = "a\nb\nc\nd\n";
+        let slice = slice_lines(text, Range::parse("2-10").unwrap());
+        assert_eq!(slice.total, 4);
+        assert_eq!(slice.start, 2);
+        assert_eq!(slice.lines, vec!["b", "c", "d"]);
+
+        let slice = slice_lines(text, Range::parse("9-").unwrap());
+        assert!(slice.lines.is_empty());
+    }
+
+    #[test]
+    fn message_slices_skip_blank_lines() {
+        let text = "{\"n\":1}\n\n{\"n\":2}\n{\"n\":3}\n";
+        let slice = slice_messages(text, Range::parse("2-3").unwrap());
+        assert_eq!(slice.total, 3);
+        assert_eq!(slice.messages.len(), 2);
+        assert_eq!(slice.messages[0].line, 3);
+        assert_eq!(slice.messages[1].value["n"], 3);
+
+        assert_eq!(
+            excerpt(text, RangeKind::Messages, Range::parse("2-").unwrap()),
+            "{\"n\":2}\n{\"n\":3}\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::convert::Infallible;
+
+use axum::http::{header::CONTENT_TYPE, HeaderMap};
+use bytes::Bytes;
+use serde_json::{Map, Value};
+
+use crate::{
+    error::ApiError,
+    util::{parse_content_type, ContentKind},
+};
+
+pub const META_HEADER_PREFIX: &str = "x-prompt-meta-";
+pub const MAX_METADATA_BYTES: usize = 16 * 1024;
+
+/// An uploaded body plus the request metadata that came with it.
+pub struct Upload {
+    pub kind: ContentKind,
+    pub body: Bytes,
+    pub metadata: Option<Map<String, Value>>,
+}
+
+/// Parses an upload. The body is either raw content (typed by `Content-Type`) or
+/// `multipart/form-data` with a `content` part and an optional JSON `metadata` part.
+/// `X-Prompt-Meta-*` headers add string metadata; keys from the JSON part win.
+pub async fn parse(headers: &HeaderMap, body: Bytes) -> Result<Upload, ApiError> {
+    let header_meta = metadata_from_headers(headers)?;
+
+    let boundary = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| v.to_ascii_lowercase().starts_with("multipart/form-data"))
+        .map(|v| {
+            multer::parse_boundary(v)
+                .map_err(|e| ApiError::BadRequest(format!("invalid multipart boundary: {e}")))
+        })
+        .transpose()?;
+
+    let (kind, body, part_meta) = match boundary {
+        Some(boundary) => parse_multipart(body, boundary).await?,
+        None => (parse_content_type(headers)?, body, None),
+    };
+
+    let metadata = match (header_meta, part_meta) {
+        (None, None) => None,
+        (Some(meta), None) | (None, Some(meta)) => Some(meta),
+        (Some(mut meta), Some(part)) => {
+            meta.extend(part);
+            Some(meta)
+        }
+    };
+    if let Some(meta) = &metadata {
+        validate_metadata(meta)?;
+    }
+
+    Ok(Upload {
+        kind,
+        body,
+        metadata,
+    })
+}
+
+async fn parse_multipart(
+    body: Bytes,
+    boundary: String,
+) -> Result<(ContentKind, Bytes, Option<Map<String, Value>>), ApiError> {
+    let stream = futures::stream::once(async move { Ok::<_, Infallible>(body) });
+    let mut multipart = multer::Multipart::new(stream, boundary);
+    let bad = |e: multer::Error| ApiError::BadRequest(format!("invalid multipart body: {e}"));
+
+    let mut content = None;
+    let mut metadata = None;
+    while let Some(field) = multipart.next_field().await.map_err(bad)? {
+        match field.name() {
+            Some("content") => {
+                let mut part_headers = HeaderMap::new();
+                if let Some(mime) = field.content_type() {
+                    part_headers.insert(
+                        CONTENT_TYPE,
+                        mime.as_ref().parse().map_err(|_| {
+                            ApiError::BadRequest("invalid content-type".to_string())
+                        })?,
+                    );
+                }
+                let kind = parse_content_type(&part_headers)?;
+                content = Some((kind, field.bytes().await.map_err(bad)?));
+            }
+            Some("metadata") => {
+                let bytes = field.bytes().await.map_err(bad)?;
+                match serde_json::from_slice::<Value>(&bytes) {
+                    Ok(Value::Object(map)) => metadata = Some(map),
+                    _ => {
+                        return Err(ApiError::BadRequest(
+                            "metadata part must be a JSON object".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let (kind, body) =
+        content.ok_or_else(|| ApiError::BadRequest("missing content part".to_string()))?;
+    Ok((kind, body, metadata))
+}
+
+/// `X-Prompt-Meta-Agent-Name: claude` becomes `{"agent_name": "claude"}`.
+fn metadata_from_headers(headers: &HeaderMap) -> Result<Option<Map<String, Value>>, ApiError> {
+    let mut meta = Map::new();
+    for (name, value) in headers {
+        let Some(key) = name.as_str().strip_prefix(META_HEADER_PREFIX) else {
+            continue;
+        };
+        if key.is_empty() {
+            continue;
+        }
+        let value = value
+            .to_str()
+            .map_err(|_| ApiError::BadRequest(format!("invalid header {name}")))?;
+        meta.insert(key.replace('-', "_"), Value::String(value.to_string()));
+    }
+    Ok((!meta.is_empty()).then_some(meta))
+}
+
+pub fn validate_metadata(meta: &Map<String, Value>) -> Result<(), ApiError> {
+    let size = serde_json::to_vec(meta)
+        .map_err(|e| ApiError::Internal(e.to_string()))?
+        .len();
+    if size > MAX_METADATA_BYTES {
+        return Err(ApiError::BadRequest(format!(
+            "metadata exceeds {MAX_METADATA_BYTES} bytes"
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn header_metadata_is_normalized() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/markdown".parse().unwrap());
+        headers.insert("x-prompt-meta-agent-name", "claude".parse().unwrap());
+        let upload = parse(&headers, Bytes::from_static(b"# hi")).await.unwrap();
+        assert_eq!(upload.kind, ContentKind::Markdown);
+        assert_eq!(upload.metadata.unwrap()["agent_name"], "claude");
+    }
+
+    #[tokio::test]
+    async fn multipart_with_metadata_part() {
+        let body = "--b\r\n\
+            Content-Disposition: form-data; name=\"metadata\"\r\n\
+            Content-Type: application/json\r\n\r\n\
+            {\"model\":\"opus\",\"agent_name\":\"json\"}\r\n\
+            --b\r\n\
+            Content-Disposition: form-data; name=\"content\"; filename=\"s.jsonl\"\r\n\
+            Content-Type: application/x-ndjson\r\n\r\n\
+            {\"a\":1}\n\r\n\
+            --b--\r\n";
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            "multipart/form-data; boundary=b".parse().unwrap(),
+        );
+        headers.insert("x-prompt-meta-agent-name", "header".parse().unwrap());
+        let upload = parse(&headers, Bytes::from(body)).await.unwrap();
+        assert_eq!(upload.kind, ContentKind::Jsonl);
+        assert_eq!(upload.body.as_ref(), b"{\"a\":1}\n");
+        let meta = upload.metadata.unwrap();
+        assert_eq!(meta["model"], "opus");
+        assert_eq!(meta["agent_name"], "json");
+    }
+}
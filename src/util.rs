@@ -1,12 +1,14 @@
 use axum::http::{header::CONTENT_TYPE, HeaderMap};
 use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hmac::{Hmac, Mac};
 use rand::{rngs::OsRng, RngCore};
 use sha2::{Digest, Sha256};
 use uuid::Uuid;
 
 use crate::error::ApiError;
 
-pub const MAX_UPLOAD_BYTES: usize = 1_048_576;
+/// Largest request body the API accepts at all; each tier sets its own, lower upload limit.
+pub const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum ContentKind {
@@ -22,6 +24,25 @@ impl ContentKind {
         }
     }
 
+    /// Accepts the canonical types and their common aliases.
+    pub fn from_mime(mime: &str) -> Option<Self> {
+        match mime {
+            "text/markdown" | "text/x-markdown" => Some(ContentKind::Markdown),
+            "application/x-ndjson" | "application/jsonl" | "application/jsonlines" => {
+                Some(ContentKind::Jsonl)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn from_canonical(content_type: &str) -> Option<Self> {
+        match content_type {
+            "text/markdown" => Some(ContentKind::Markdown),
+            "application/x-ndjson" => Some(ContentKind::Jsonl),
+            _ => None,
+        }
+    }
+
     pub fn response_type(self) -> &'static str {
         match self {
             ContentKind::Markdown => "text/markdown; charset=utf-8",
@@ -44,17 +65,15 @@ pub fn parse_content_type(headers: &HeaderMap) -> Result<ContentKind, ApiError>
         .to_str()
         .map_err(|_| ApiError::BadRequest("invalid content-type".to_string()))?;
 
-    let base = raw.split(';').next().unwrap_or("").trim().to_ascii_lowercase();
+    let base = raw
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
 
-    match base.as_str() {
-        "text/markdown" | "text/x-markdown" => Ok(ContentKind::Markdown),
-        "application/x-ndjson" | "application/jsonl" | "application/jsonlines" => {
-            Ok(ContentKind::Jsonl)
-        }
-        _ => Err(ApiError::BadRequest(format!(
-            "unsupported content-type: {base}"
-        ))),
-    }
+    ContentKind::from_mime(&base)
+        .ok_or_else(|| ApiError::BadRequest(format!("unsupported content-type: {base}")))
 }
 
 pub fn generate_api_key() -> String {
@@ -64,13 +83,17 @@ pub fn generate_api_key() -> String {
     format!("prq_{encoded}")
 }
 
-pub fn hash_api_key(key: &str, pepper: Option<&str>) -> String {
-    let mut hasher = Sha256::new();
-    if let Some(pepper) = pepper {
-        hasher.update(pepper.as_bytes());
-    }
-    hasher.update(key.as_bytes());
-    hex::encode(hasher.finalize())
+/// Token for reading an unlisted request, passed as `?token=`.
+pub fn generate_share_token() -> String {
+    let mut bytes = [0u8; 24];
+    OsRng.fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+/// Number of lines in `bytes`, as `str::lines` would count them, without decoding.
+pub fn count_lines(bytes: &[u8]) -> usize {
+    let newlines = bytes.iter().filter(|&&b| b == b'\n').count();
+    newlines + usize::from(!bytes.is_empty() && !bytes.ends_with(b"\n"))
 }
 
 pub fn sha256_hex(bytes: &[u8]) -> String {
@@ -79,6 +102,16 @@ pub fn sha256_hex(bytes: &[u8]) -> String {
     hex::encode(hasher.finalize())
 }
 
+/// HMAC-SHA256 keyed with `secret` over `parts` in order. Finalize it for a signature, or
+/// check one with `verify_slice`, which compares in constant time.
+pub fn hmac_sha256(secret: &[u8], parts: &[&[u8]]) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts any key length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac
+}
+
 pub fn object_key(uuid: Uuid, rev: i32, kind: ContentKind) -> String {
     format!("requests/{uuid}/rev-{rev}.{}", kind.extension())
 }
@@ -87,6 +120,30 @@ pub fn object_key(uuid: Uuid, rev: i32, kind: ContentKind) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn counts_lines_like_str_lines() {
+        for text in ["", "a", "a\n", "a\nb", "a\r\nb\r\n", "\n\n", "a\n\nb\n"] {
+            assert_eq!(
+                count_lines(text.as_bytes()),
+                text.lines().count(),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn hmac_sha256_covers_parts_in_order() {
+        // RFC 4231, test case 2.
+        let mac = hmac_sha256(b"Jefe", &[b"what do ya want ", b"for nothing?"]);
+        assert_eq!(
+            hex::encode(mac.finalize().into_bytes()),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+        let sig = hmac_sha256(b"Jefe", &[b"ab", b"c"]).finalize().into_bytes();
+        assert!(hmac_sha256(b"Jefe", &[b"abc"]).verify_slice(&sig).is_ok());
+        assert!(hmac_sha256(b"Jefe", &[b"cab"]).verify_slice(&sig).is_err());
+    }
+
     #[test]
     fn parse_markdown() {
         let mut headers = HeaderMap::new();
@@ -2,15 +2,21 @@ use std::net::SocketAddr;
 
 use serde::Deserialize;
 
-#[tokio::test]
-async fn e2e_markdown_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
-    if std::env::var("E2E").is_err() {
-        eprintln!("E2E not set, skipping");
-        return Ok(());
-    }
+#[derive(Deserialize)]
+struct AccountResp {
+    api_key: String,
+}
 
+#[derive(Deserialize)]
+struct CreateResp {
+    uuid: String,
+}
+
+/// Serves the app on a random port and returns its base URL and database pool.
+async fn spawn_app() -> Result<(String, sqlx::PgPool), Box<dyn std::error::Error>> {
     let cfg = prompt_request::config::Config::from_env()?;
     let state = prompt_request::build_state(&cfg).await?;
+    let pool = state.pool.clone();
     let app = prompt_request::build_router(state);
 
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await?;
@@ -24,26 +30,23 @@ async fn e2e_markdown_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
         .unwrap();
     });
 
-    let base = format!("http://{}", addr);
-    let client = reqwest::Client::new();
+    Ok((format!("http://{}", addr), pool))
+}
 
-    #[derive(Deserialize)]
-    struct AccountResp {
-        api_key: String,
+#[tokio::test]
+async fn e2e_markdown_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::var("E2E").is_err() {
+        eprintln!("E2E not set, skipping");
+        return Ok(());
     }
 
-    let acct = client
-        .post(format!("{}/api/accounts", base))
-        .send()
-        .await?;
+    let (base, _) = spawn_app().await?;
+    let client = reqwest::Client::new();
+
+    let acct = client.post(format!("{}/api/accounts", base)).send().await?;
     assert!(acct.status().is_success());
     let acct: AccountResp = acct.json().await?;
 
-    #[derive(Deserialize)]
-    struct CreateResp {
-        uuid: String,
-    }
-
     let create = client
         .post(format!("{}/api/requests", base))
         .header("Authorization", format!("Bearer {}", acct.api_key))
@@ -64,3 +67,73 @@ async fn e2e_markdown_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test]
+async fn e2e_blame_skips_expired_revisions() -> Result<(), Box<dyn std::error::Error>> {
+    if std::env::var("E2E").is_err() {
+        eprintln!("E2E not set, skipping");
+        return Ok(());
+    }
+
+    let (base, pool) = spawn_app().await?;
+    let client = reqwest::Client::new();
+
+    let acct: AccountResp = client
+        .post(format!("{}/api/accounts", base))
+        .send()
+        .await?
+        .json()
+        .await?;
+    let auth = format!("Bearer {}", acct.api_key);
+
+    let create: CreateResp = client
+        .post(format!("{}/api/requests", base))
+        .header("Authorization", &auth)
+        .header("Content-Type", "text/markdown")
+        .body("one\n")
+        .send()
+        .await?
+        .json()
+        .await?;
+    for body in ["one\ntwo\n", "one\ntwo\nthree\n"] {
+        let put = client
+            .put(format!("{}/api/requests/{}", base, create.uuid))
+            .header("Authorization", &auth)
+            .header("Content-Type", "text/markdown")
+            .body(body)
+            .send()
+            .await?;
+        assert!(put.status().is_success());
+    }
+
+    #[derive(Deserialize)]
+    struct BlameLine {
+        rev: i32,
+    }
+    #[derive(Deserialize)]
+    struct BlameResp {
+        lines: Vec<BlameLine>,
+    }
+    let blame = || async {
+        let resp = client
+            .get(format!("{}/api/requests/{}/blame?rev=3", base, create.uuid))
+            .header("Authorization", &auth)
+            .send()
+            .await?;
+        assert!(resp.status().is_success());
+        let blame: BlameResp = resp.json().await?;
+        Ok::<_, reqwest::Error>(blame.lines.iter().map(|l| l.rev).collect::<Vec<_>>())
+    };
+    assert_eq!(blame().await?, vec![1, 2, 3]);
+
+    sqlx::query(
+        "UPDATE request_revisions SET expires_at = now() \
+         WHERE request_uuid = $1::uuid AND rev_number = 1",
+    )
+    .bind(&create.uuid)
+    .execute(&pool)
+    .await?;
+    assert_eq!(blame().await?, vec![2, 2, 3]);
+
+    Ok(())
+}